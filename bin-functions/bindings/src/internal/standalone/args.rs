@@ -31,6 +31,44 @@ pub fn set_result(value: &json::Value) -> Result<(), json::Error> {
     Ok(())
 }
 
+/// Where the status code and headers of the response are stored, next to the output file
+const RESULT_META_PATH: &str = "/tmp/output.meta";
+
+fn update_result_meta<F: FnOnce(&mut json::Map<String, json::Value>)>(update: F) {
+    let mut meta = match std::fs::read_to_string(RESULT_META_PATH) {
+        Ok(content) => json::from_str(&content).expect("Invalid result metadata"),
+        Err(_) => json::Map::new(),
+    };
+
+    update(&mut meta);
+
+    let content = json::to_string(&meta).unwrap();
+    std::fs::write(RESULT_META_PATH, content).expect("Failed to write result metadata");
+}
+
+/// Sets the HTTP status code of the response (200 by default)
+pub fn set_status(status: u16) {
+    update_result_meta(|meta| {
+        meta.insert("status".to_string(), status.into());
+    });
+}
+
+/// Adds a header to the HTTP response
+///
+/// Can be called multiple times for the same header to send multiple values.
+pub fn set_header(name: &str, value: &str) {
+    update_result_meta(|meta| {
+        let headers = meta
+            .entry("headers")
+            .or_insert_with(|| json::Value::Array(vec![]));
+
+        headers
+            .as_array_mut()
+            .expect("Invalid result metadata")
+            .push(json::json!([name, value]));
+    });
+}
+
 pub fn get_unix_time() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
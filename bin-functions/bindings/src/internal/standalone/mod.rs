@@ -2,3 +2,4 @@ pub mod args;
 pub mod config;
 pub mod ipc;
 pub mod log;
+pub mod request;
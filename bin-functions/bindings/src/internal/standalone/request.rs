@@ -0,0 +1,27 @@
+//! Native functions receive the request metadata through environment variables
+
+use std::env;
+
+pub fn get_method() -> String {
+    env::var("OL_REQUEST_METHOD").unwrap_or_else(|_| "POST".to_string())
+}
+
+pub fn get_path() -> String {
+    env::var("OL_REQUEST_PATH").unwrap_or_default()
+}
+
+pub fn get_query() -> String {
+    env::var("OL_REQUEST_QUERY").unwrap_or_default()
+}
+
+pub fn get_headers() -> Vec<(String, String)> {
+    match env::var("OL_REQUEST_HEADERS") {
+        Ok(headers) => serde_json::from_str(&headers).expect("Failed to parse request headers"),
+        Err(_) => vec![],
+    }
+}
+
+/// Not set by the native runtime, as it does not authenticate clients
+pub fn get_identity_json() -> Option<String> {
+    env::var("OL_REQUEST_IDENTITY").ok()
+}
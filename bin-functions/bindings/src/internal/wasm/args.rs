@@ -7,6 +7,8 @@ mod api {
     extern "C" {
         pub fn get_args(len_out: *mut u64) -> i64;
         pub fn set_result(buf_ptr: *const u8, buf_len: u32);
+        pub fn set_status(status: u32);
+        pub fn set_header(name_ptr: *const u8, name_len: u32, value_ptr: *const u8, value_len: u32);
         pub fn get_unix_time() -> u64;
         pub fn get_random_value(buf_ptr: *mut u8, buf_len: u32);
     }
@@ -60,3 +62,22 @@ pub fn set_result(value: &json::Value) -> Result<(), json::Error> {
 
     Ok(())
 }
+
+/// Sets the HTTP status code of the response (200 by default)
+pub fn set_status(status: u16) {
+    unsafe { api::set_status(status as u32) };
+}
+
+/// Adds a header to the HTTP response
+///
+/// Can be called multiple times for the same header to send multiple values.
+pub fn set_header(name: &str, value: &str) {
+    unsafe {
+        api::set_header(
+            name.as_bytes().as_ptr(),
+            name.len() as u32,
+            value.as_bytes().as_ptr(),
+            value.len() as u32,
+        )
+    };
+}
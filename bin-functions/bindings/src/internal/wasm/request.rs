@@ -0,0 +1,57 @@
+mod api {
+    #[link(wasm_import_module = "ol_request")]
+    extern "C" {
+        pub fn get_method(len_out: *mut u64) -> i64;
+        pub fn get_path(len_out: *mut u64) -> i64;
+        pub fn get_query(len_out: *mut u64) -> i64;
+        pub fn get_headers(len_out: *mut u64) -> i64;
+        pub fn get_identity(len_out: *mut u64) -> i64;
+    }
+}
+
+/// Takes ownership of a buffer allocated by the host
+fn read_buffer(getter: unsafe extern "C" fn(*mut u64) -> i64) -> Vec<u8> {
+    let mut len = 0u64;
+    let data_ptr = unsafe { getter((&mut len) as *mut u64) };
+
+    if data_ptr == 0 {
+        return vec![];
+    }
+
+    if data_ptr < 0 {
+        panic!("Got unexpected error");
+    }
+
+    let len = len as usize;
+    unsafe { Vec::<u8>::from_raw_parts(data_ptr as *mut u8, len, len) }
+}
+
+fn read_string(getter: unsafe extern "C" fn(*mut u64) -> i64) -> String {
+    String::from_utf8(read_buffer(getter)).expect("Got invalid string from host")
+}
+
+pub fn get_method() -> String {
+    read_string(api::get_method)
+}
+
+pub fn get_path() -> String {
+    read_string(api::get_path)
+}
+
+pub fn get_query() -> String {
+    read_string(api::get_query)
+}
+
+pub fn get_headers() -> Vec<(String, String)> {
+    bincode::deserialize(&read_buffer(api::get_headers)).unwrap()
+}
+
+pub fn get_identity_json() -> Option<String> {
+    let identity = read_string(api::get_identity);
+
+    if identity.is_empty() {
+        None
+    } else {
+        Some(identity)
+    }
+}
@@ -9,6 +9,9 @@ pub use config::*;
 mod ipc;
 pub use crate::ipc::*;
 
+mod request;
+pub use request::*;
+
 pub mod log;
 
 pub mod internal;
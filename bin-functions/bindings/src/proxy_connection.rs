@@ -39,6 +39,12 @@ impl ProxyConnection {
         let cdata = FuncCallData {
             fn_name,
             args: ByteBuf::from(args),
+            // Set by the runtime if the invocation is part of a trace
+            trace_context: std::env::var("TRACEPARENT").ok(),
+            credentials: ["authorization", "x-api-key"]
+                .into_iter()
+                .filter_map(|name| Some((name.to_string(), crate::get_header(name)?)))
+                .collect(),
         };
         self.send_message(&ProxyMessage::FuncCallRequest(cdata));
 
@@ -0,0 +1,95 @@
+pub use crate::internal::request::*;
+
+/// A client verified by the worker
+pub struct Identity {
+    /// How the client authenticated ("api_key" or "jwt")
+    pub method: String,
+    /// The name of the API key or the subject of the token
+    pub subject: String,
+    /// The functions the client may invoke (`None` if unrestricted)
+    pub functions: Option<Vec<String>>,
+    /// All claims of the token (empty for API keys)
+    pub claims: serde_json::Value,
+}
+
+/// Returns the client that invoked this function
+///
+/// Returns `None` if the worker does not require authentication.
+pub fn get_identity() -> Option<Identity> {
+    let identity: serde_json::Value =
+        serde_json::from_str(&get_identity_json()?).expect("Got invalid identity from host");
+
+    let get_str = |key: &str| identity[key].as_str().unwrap_or_default().to_string();
+
+    let functions = identity["functions"].as_array().map(|functions| {
+        functions
+            .iter()
+            .filter_map(|function| function.as_str().map(str::to_string))
+            .collect()
+    });
+
+    Some(Identity {
+        method: get_str("method"),
+        subject: get_str("subject"),
+        functions,
+        claims: identity["claims"].clone(),
+    })
+}
+
+/// Returns the value of a request header (case-insensitive)
+///
+/// If the header was sent multiple times, only the first value is returned.
+pub fn get_header(name: &str) -> Option<String> {
+    get_headers()
+        .into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+/// Returns the decoded key-value pairs of the query string
+pub fn get_query_params() -> Vec<(String, String)> {
+    get_query()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (decode_query_part(key), decode_query_part(value)),
+            None => (decode_query_part(pair), String::new()),
+        })
+        .collect()
+}
+
+/// Returns the first value of a query parameter
+pub fn get_query_param(name: &str) -> Option<String> {
+    get_query_params()
+        .into_iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+/// Decodes `+` and percent-encoded bytes
+fn decode_query_part(part: &str) -> String {
+    let bytes = part.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        match bytes[pos] {
+            b'+' => result.push(b' '),
+            b'%' if pos + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[pos + 1..pos + 3]).ok();
+
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        result.push(byte);
+                        pos += 2;
+                    }
+                    None => result.push(b'%'),
+                }
+            }
+            byte => result.push(byte),
+        }
+        pos += 1;
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
@@ -0,0 +1,18 @@
+use open_lambda::{get_args, get_unix_time};
+
+/// Keeps running for `{"secs": <seconds>}` to simulate a slow function
+///
+/// Guests cannot sleep, so this spins until the time has passed.
+#[open_lambda_macros::main_func]
+fn main() {
+    let args = get_args().expect("No argument given");
+    let secs = args
+        .get("secs")
+        .and_then(|secs| secs.as_u64())
+        .expect("Could not find `secs` argument");
+
+    let deadline = get_unix_time() + secs;
+    while get_unix_time() < deadline {
+        std::hint::spin_loop();
+    }
+}
@@ -0,0 +1,29 @@
+use open_lambda::{
+    get_header, get_identity, get_method, get_path, get_query_params, json, set_header, set_result,
+    set_status,
+};
+
+/// Returns the metadata of the HTTP request that triggered the call
+///
+/// The `status` query parameter sets the status code of the response.
+#[open_lambda_macros::main_func]
+fn main() {
+    let query: json::Map<String, json::Value> = get_query_params()
+        .into_iter()
+        .map(|(key, value)| (key, json::Value::String(value)))
+        .collect();
+
+    if let Some(status) = query.get("status").and_then(|s| s.as_str()) {
+        set_status(status.parse().expect("Invalid status code"));
+    }
+    set_header("x-echo-method", &get_method());
+
+    set_result(&json::json!({
+        "method": get_method(),
+        "path": get_path(),
+        "query": query,
+        "user_agent": get_header("user-agent"),
+        "identity": get_identity().map(|identity| identity.subject),
+    }))
+    .unwrap();
+}
@@ -1,6 +1,21 @@
-use open_lambda::{function_call, json};
+use open_lambda::{function_call, get_args, json, set_result, set_status};
 
+/// Calls another function (`noop` unless `{"function": <name>}` is given)
+///
+/// Errors of the callee are returned with status 502.
 #[open_lambda_macros::main_func]
 fn main() {
-    function_call("noop", &json::Value::Null).expect("Function call failed");
+    let args = get_args().unwrap_or(json::Value::Null);
+    let func_name = args
+        .get("function")
+        .and_then(|name| name.as_str())
+        .unwrap_or("noop");
+
+    match function_call(func_name, &json::Value::Null) {
+        Ok(result) => set_result(&json::json!({ "result": result })).unwrap(),
+        Err(err) => {
+            set_status(502);
+            set_result(&json::json!({ "error": err })).unwrap();
+        }
+    }
 }
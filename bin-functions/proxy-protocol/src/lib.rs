@@ -5,6 +5,11 @@ use serde_bytes::ByteBuf;
 pub struct FuncCallData {
     pub fn_name: String,
     pub args: ByteBuf,
+    /// The W3C `traceparent` of the calling invocation (if any)
+    pub trace_context: Option<String>,
+    /// The `Authorization` and `X-API-Key` headers of the calling invocation, so that
+    /// the call is made on behalf of the original client
+    pub credentials: Vec<(String, String)>,
 }
 
 pub type CallResult = Result<ByteBuf, String>;
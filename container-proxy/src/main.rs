@@ -15,6 +15,8 @@ use open_lambda_proxy_protocol::ProxyMessage;
 
 use tokio_uring_executor as executor;
 
+mod metrics;
+
 fn main() {
     executor::initialize();
 
@@ -30,12 +32,23 @@ async fn main_logic() {
 
     let container_dir = argv.next().expect("No container directory given");
 
+    // Optional address (e.g., "localhost:9100") to expose Prometheus metrics at.
+    // The worker starts one proxy per container and does not pass this (each proxy would
+    // need its own port), so it is only used when running the proxy manually.
+    let metrics_address = argv.next();
+
     simple_logging::log_to_file(
         format!("{container_dir}/container-proxy.log"),
         log::LevelFilter::Info,
     )
     .unwrap();
 
+    if let Some(address) = metrics_address {
+        unsafe {
+            executor::unsafe_spawn(metrics::serve(address));
+        }
+    }
+
     let path = format!("{container_dir}/proxy.sock");
     let listener = UnixListener::bind(path).unwrap();
 
@@ -71,7 +84,12 @@ async fn main_logic() {
     }
 }
 
-async fn function_call(func_name: String, args: Vec<u8>) -> Result<Vec<u8>, String> {
+async fn function_call(
+    func_name: String,
+    args: Vec<u8>,
+    trace_context: Option<String>,
+    credentials: Vec<(String, String)>,
+) -> Result<Vec<u8>, String> {
     log::trace!("Issuing function call to {func_name}");
 
     let server_addr = "localhost:5000";
@@ -81,7 +99,17 @@ async fn function_call(func_name: String, args: Vec<u8>) -> Result<Vec<u8>, Stri
         .build()
         .expect("Failed to set up HTTP client");
 
-    let request = client.post(url).body(args);
+    let mut request = client.post(url).body(args);
+
+    // Lets the worker correlate the call with the invocation that issued it
+    if let Some(traceparent) = trace_context {
+        request = request.header("traceparent", traceparent);
+    }
+
+    // Internal calls are made on behalf of the original client
+    for (name, value) in credentials {
+        request = request.header(name, value);
+    }
 
     let result = match request.send().await {
         Ok(result) => result,
@@ -128,7 +156,22 @@ async fn handle_connection(stream: UnixStream) {
 
         let response = match msg {
             ProxyMessage::FuncCallRequest(call_data) => {
-                let result = function_call(call_data.fn_name, call_data.args.into_vec()).await;
+                metrics::FUNC_CALL_REQUESTS.inc();
+                let timer = metrics::FUNC_CALL_LATENCY.start_timer();
+
+                let result = function_call(
+                    call_data.fn_name,
+                    call_data.args.into_vec(),
+                    call_data.trace_context,
+                    call_data.credentials,
+                )
+                .await;
+
+                timer.observe_duration();
+                if result.is_err() {
+                    metrics::FUNC_CALL_ERRORS.inc();
+                }
+
                 ProxyMessage::FuncCallResult(result.map(ByteBuf::from))
             }
             _ => {
@@ -0,0 +1,81 @@
+use lazy_static::lazy_static;
+
+use prometheus::{
+    register_histogram, register_int_counter, Encoder, Histogram, IntCounter, TextEncoder,
+};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use tokio_uring_executor as executor;
+
+lazy_static! {
+    pub static ref FUNC_CALL_REQUESTS: IntCounter = register_int_counter!(
+        "ol_proxy_func_call_requests_total",
+        "Number of function call requests received from the runtime"
+    )
+    .unwrap();
+    pub static ref FUNC_CALL_ERRORS: IntCounter = register_int_counter!(
+        "ol_proxy_func_call_errors_total",
+        "Number of function call requests that failed"
+    )
+    .unwrap();
+    pub static ref FUNC_CALL_LATENCY: Histogram = register_histogram!(
+        "ol_proxy_func_call_duration_seconds",
+        "Time it takes to process a function call request"
+    )
+    .unwrap();
+}
+
+/// Serves the metrics at the given address
+///
+/// This only implements the minimum of HTTP needed for Prometheus to scrape it;
+/// every request gets the current metrics in response.
+pub async fn serve(address: String) {
+    let listener = match TcpListener::bind(&address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to bind metrics listener at {address}: {err}");
+            return;
+        }
+    };
+
+    log::info!("Serving metrics at http://{address}/metrics");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => unsafe {
+                executor::unsafe_spawn(async move {
+                    if let Err(err) = handle_request(stream).await {
+                        log::warn!("Failed to serve metrics: {err}");
+                    }
+                });
+            },
+            // Errors such as running out of file descriptors are usually temporary,
+            // and metrics should not stop being served because of a single bad connection
+            Err(err) => log::warn!("Failed to accept metrics connection: {err}"),
+        }
+    }
+}
+
+async fn handle_request(mut stream: TcpStream) -> std::io::Result<()> {
+    // Wait for the request, but we do not care about its content
+    let mut buffer = [0; 1024];
+    let _ = stream.read(&mut buffer).await?;
+
+    let encoder = TextEncoder::new();
+    let mut body = vec![];
+    encoder
+        .encode(&prometheus::gather(), &mut body)
+        .map_err(std::io::Error::other)?;
+
+    let header = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        encoder.format_type(),
+        body.len()
+    );
+
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await
+}
@@ -26,6 +26,30 @@ class OpenLambda:
 
         return resp.text
 
+    def invoke_async(self, fn_name, args):
+        ''' Start a serverless function in the background and return the invocation id '''
+
+        resp = self._post(f"invoke-async/{fn_name}", args)
+        if resp.status_code != 202:
+            msg = f'"invoke_async" failed with status code {resp.status_code}: {resp.text}'
+            raise requests.HTTPError(msg)
+
+        return resp.json()["id"]
+
+    def get_invocation(self, invocation_id):
+        ''' Returns the response for an asynchronous invocation
+
+        The status code is 202 as long as the invocation is still running '''
+
+        return self._session.get(f"http://{self._address}/invocations/{invocation_id}")
+
+    def cancel_invocation(self, invocation_id):
+        ''' Cancel an asynchronous invocation or discard its result '''
+
+        resp = self._session.delete(f"http://{self._address}/invocations/{invocation_id}")
+        self._check_status_code(resp, "cancel_invocation")
+        return resp.json()
+
     def create(self, args):
         ''' Create a new sandbox '''
 
@@ -5,9 +5,23 @@
 # pylint: disable=missing-function-docstring, consider-using-with
 
 import argparse
+import base64
+import hashlib
+import hmac
+import http.client
+import json
 import os
+import shutil
+import socket
+import subprocess
+import tempfile
 
-from time import time
+from concurrent.futures import ThreadPoolExecutor
+from contextlib import contextmanager
+
+from time import time, sleep
+
+import requests
 
 from open_lambda import OpenLambda
 
@@ -46,6 +60,14 @@ def internal_call():
     open_lambda = OpenLambda()
     open_lambda.run("internal-call", args=[], json=False)
 
+@test
+def failed_internal_call():
+    # Errors of the callee are passed on to the calling function
+    resp = requests.post("http://localhost:5000/run/internal-call",
+                         json={"function": "does-not-exist"}, timeout=10)
+    assert_eq(resp.status_code, 502)
+    assert "404" in resp.json()["error"]
+
 @test
 def hashing():
     open_lambda = OpenLambda()
@@ -57,7 +79,897 @@ def multiply():
     result = open_lambda.run("multiply", args={"left": 25, "right": 8}, json=True)
     assert_eq(result["result"], 200)
 
-def run_tests():
+@test
+def stats():
+    open_lambda = OpenLambda()
+    open_lambda.run("noop", args=[], json=False)
+
+    result = open_lambda.get_statistics()
+    assert result["noop.cnt"] >= 1
+    assert_eq(result["noop.cnt"], result["noop.cold-starts"] + result["noop.warm-starts"])
+
+@test
+def request_metadata():
+    resp = requests.get("http://localhost:5000/run/echo-request/users/42?name=a+b&path=%2Ftmp",
+                        headers={"User-Agent": "ol-test"}, timeout=10)
+    assert_eq(resp.status_code, 200)
+
+    result = resp.json()
+    assert_eq(result["method"], "GET")
+    assert_eq(result["path"], "/users/42")
+    assert_eq(result["query"], {"name": "a b", "path": "/tmp"})
+    assert_eq(result["user_agent"], "ol-test")
+    assert_eq(resp.headers["X-Echo-Method"], "GET")
+
+@test
+def custom_status():
+    resp = requests.post("http://localhost:5000/run/echo-request?status=404", timeout=10)
+    assert_eq(resp.status_code, 404)
+    assert_eq(resp.headers["X-Echo-Method"], "POST")
+    assert_eq(resp.json()["method"], "POST")
+
+@test
+def timeouts():
+    with _extra_worker("--listen-address", "localhost:5007", "-S", "busy-wait.timeout_ms=500"):
+        start = time()
+        resp = _post_when_ready("http://localhost:5007/run/busy-wait", json={"secs": 10})
+        assert_eq(resp.status_code, 504)
+        assert_eq(resp.json()["stage"], "timeout")
+        assert time() - start < 5
+
+        # The worker keeps serving calls afterwards
+        resp = requests.post("http://localhost:5007/run/multiply", json={"left": 25, "right": 8},
+                             timeout=10)
+        assert_eq(resp.status_code, 200)
+
+@test
+def fuel_limits():
+    with _extra_worker("--listen-address", "localhost:5014", "-S", "*.fuel=100000000",
+                       "-S", "busy-wait.fuel_yield_interval=10000"):
+        resp = _post_when_ready("http://localhost:5014/run/multiply", json={"left": 25, "right": 8})
+        assert_eq(resp.status_code, 200)
+        assert int(resp.headers["X-OL-Fuel-Consumed"]) > 0
+
+        # Spinning guests trap once their fuel runs out
+        start = time()
+        resp = requests.post("http://localhost:5014/run/busy-wait", json={"secs": 30}, timeout=40)
+        assert_eq(resp.status_code, 500)
+        assert_eq(resp.json()["stage"], "trap")
+        assert "fuel" in resp.json()["trap"]
+        assert time() - start < 20
+
+    # Yielding without consuming any fuel is rejected on startup
+    with _extra_worker("--listen-address", "localhost:5014",
+                       "-S", "busy-wait.fuel_yield_interval=0") as worker:
+        assert worker.wait(timeout=10) != 0
+
+@test
+def memory_limits():
+    def hash_args(input_len):
+        return {"num_hashes": 1, "input_len": input_len}
+
+    with _extra_worker("--listen-address", "localhost:5018",
+                       "-S", "hashing.max_memory_bytes=4194304",
+                       "-S", "multiply.max_memory_bytes=65536"):
+        url = "http://localhost:5018/run"
+
+        resp = _post_when_ready(f"{url}/hashing", json=hash_args(1024))
+        assert_eq(resp.status_code, 200)
+
+        # Growing the memory past the limit traps the guest
+        resp = requests.post(f"{url}/hashing", json=hash_args(16 * 1024 * 1024), timeout=30)
+        assert_eq(resp.status_code, 500)
+        assert_eq(resp.json()["stage"], "limit")
+
+        # The limit only applies to one invocation
+        assert_eq(requests.post(f"{url}/hashing", json=hash_args(1024), timeout=10)
+                  .status_code, 200)
+
+        # The initial memory of the module already exceeds the limit
+        resp = requests.post(f"{url}/multiply", json={"left": 25, "right": 8}, timeout=10)
+        assert_eq(resp.status_code, 500)
+        assert_eq(resp.json()["stage"], "limit")
+
+        # Other functions are not affected
+        assert_eq(requests.post(f"{url}/noop", json=[], timeout=10).status_code, 200)
+
+@test
+def graceful_shutdown():
+    with _extra_worker("--listen-address", "localhost:5008") as worker:
+        _post_when_ready("http://localhost:5008/run/multiply", json={"left": 25, "right": 8})
+
+        with ThreadPoolExecutor(max_workers=1) as executor:
+            call = executor.submit(requests.post, "http://localhost:5008/run/busy-wait",
+                                   json={"secs": 2}, timeout=10)
+            sleep(0.5)
+
+            # Running calls finish before the worker exits
+            worker.terminate()
+            assert_eq(call.result().status_code, 200)
+
+        assert_eq(worker.wait(timeout=10), 0)
+
+@test
+def call_queueing():
+    with _extra_worker("--listen-address", "localhost:5011",
+                       "-S", "busy-wait.max_concurrency=1", "-S", "busy-wait.max_queue_length=1",
+                       "-S", "busy-wait.queue_timeout_ms=1000"):
+        _post_when_ready("http://localhost:5011/run/multiply", json={"left": 25, "right": 8})
+
+        def call():
+            return requests.post("http://localhost:5011/run/busy-wait", json={"secs": 3},
+                                 timeout=10)
+
+        with ThreadPoolExecutor(max_workers=2) as executor:
+            running = executor.submit(call)
+            sleep(0.5)
+            queued = executor.submit(call)
+            sleep(0.2)
+
+            # The queue only has room for one call
+            resp = call()
+            assert_eq(resp.status_code, 429)
+            assert_eq(resp.json()["stage"], "queue")
+            assert int(resp.headers["Retry-After"]) > 0
+
+            # Queued calls give up once the queue timeout expires
+            resp = queued.result()
+            assert_eq(resp.status_code, 503)
+            assert_eq(resp.json()["stage"], "queue")
+
+            assert_eq(running.result().status_code, 200)
+
+@test
+def hot_reload():
+    with tempfile.TemporaryDirectory() as tmp_dir:
+        registry = os.path.join(tmp_dir, "registry")
+        os.mkdir(registry)
+        shutil.copyfile("./test-registry.wasm/multiply.wasm", os.path.join(registry, "hot.wasm"))
+
+        with _extra_worker("--listen-address", "localhost:5012", registry=registry):
+            resp = _post_when_ready("http://localhost:5012/run/hot", json={"left": 25, "right": 8})
+            assert_eq(resp.json()["result"], 200)
+
+            # The new file is older than the cached module of the old one
+            new_path = os.path.join(tmp_dir, "hot.wasm")
+            shutil.copyfile("./test-registry.wasm/echo-request.wasm", new_path)
+            os.utime(new_path, (0, 0))
+            os.rename(new_path, os.path.join(registry, "hot.wasm"))
+
+            for _ in range(50):
+                resp = requests.post("http://localhost:5012/run/hot", json={}, timeout=10)
+                if "method" in resp.json():
+                    break
+                sleep(0.1)
+
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.json()["method"], "POST")
+
+@test
+def async_invocation():
+    open_lambda = OpenLambda()
+    invocation_id = open_lambda.invoke_async("multiply", {"left": 25, "right": 8})
+
+    for _ in range(100):
+        resp = open_lambda.get_invocation(invocation_id)
+        if resp.status_code != 202:
+            break
+        sleep(0.1)
+
+    assert_eq(resp.status_code, 200)
+    assert_eq(resp.json()["result"], 200)
+
+    # Deleting a finished invocation discards its result
+    assert_eq(open_lambda.cancel_invocation(invocation_id)["previous_status"], "completed")
+    assert_eq(open_lambda.get_invocation(invocation_id).status_code, 404)
+
+def _get_status(port):
+    resp = requests.get(f"http://localhost:{port}/status", timeout=10)
+    assert_eq(resp.status_code, 200)
+    return resp.json()
+
+@test
+def invocation_expiry():
+    with tempfile.TemporaryDirectory() as config_dir:
+        config_path = os.path.join(config_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write('''
+listen_address = "localhost:5009"
+
+[invocations]
+result_ttl_secs = 1
+''')
+
+        with _extra_worker("--config", config_path):
+            resp = _post_when_ready("http://localhost:5009/invoke-async/multiply",
+                                    json={"left": 25, "right": 8})
+            assert_eq(resp.status_code, 202)
+            assert_eq(_get_status(5009)["num_invocations"], 1)
+
+            # Expired results are dropped by the periodic cleanup, even if nobody asks for them
+            for _ in range(30):
+                if _get_status(5009)["num_invocations"] == 0:
+                    break
+                sleep(1)
+
+            assert_eq(_get_status(5009)["num_invocations"], 0)
+
+def _curl_run(fn_name, args, http_flag):
+    ''' Calls a function using curl and returns the HTTP version and result '''
+    result = subprocess.run(["curl", "--silent", "--fail", http_flag,
+                             "--write-out", "\\n%{http_version}",
+                             "--data", json.dumps(args),
+                             f"http://localhost:5000/run/{fn_name}"],
+                            capture_output=True, check=True, text=True)
+    body, version = result.stdout.rsplit("\n", 1)
+    return version, json.loads(body)
+
+@test
+def http_protocols():
+    # Prior-knowledge HTTP/2 (h2c) and HTTP/1.1 must both work on the same port
+    for flag, expected_version in [("--http1.1", "1.1"), ("--http2-prior-knowledge", "2")]:
+        version, result = _curl_run("multiply", {"left": 25, "right": 8}, flag)
+        assert_eq(version, expected_version)
+        assert_eq(result["result"], 200)
+
+@contextmanager
+def _extra_worker(*args, registry="./test-registry.wasm"):
+    ''' Runs a second WebAssembly worker with the specified arguments
+
+    The worker runs in its own directory, so it cannot touch the files of the main worker.
+    '''
+    with tempfile.TemporaryDirectory() as work_dir:
+        worker = subprocess.Popen([os.path.abspath("./ol-wasm"),
+                                   "--registry-path", os.path.abspath(registry),
+                                   *args], cwd=work_dir)
+        try:
+            yield worker
+        finally:
+            worker.terminate()
+            worker.wait()
+
+def _post_when_ready(url, **kwargs):
+    ''' Sends a POST request, retrying until the worker has loaded all functions '''
+    for _ in range(100):
+        try:
+            resp = requests.post(url, timeout=10, **kwargs)
+            if resp.status_code != 503:
+                return resp
+        except requests.exceptions.ConnectionError:
+            pass
+        sleep(0.1)
+    raise RuntimeError(f"Worker at {url} did not start")
+
+def _generate_certs(cert_dir):
+    ''' Generates a test CA as well as a server and client certificate signed by it '''
+    def openssl(*args):
+        subprocess.run(["openssl", *args], cwd=cert_dir, capture_output=True, check=True)
+
+    openssl("req", "-x509", "-newkey", "rsa:2048", "-nodes", "-days", "1",
+            "-subj", "/CN=ol-test-ca", "-keyout", "ca.key", "-out", "ca.crt")
+
+    for name, extensions in [("server", "subjectAltName=DNS:localhost"),
+                             ("client", "extendedKeyUsage=clientAuth")]:
+        with open(os.path.join(cert_dir, f"{name}.ext"), 'w', encoding='utf-8') as file:
+            file.write(extensions + "\n")
+
+        openssl("req", "-newkey", "rsa:2048", "-nodes", "-subj", f"/CN={name}",
+                "-keyout", f"{name}.key", "-out", f"{name}.csr")
+        openssl("x509", "-req", "-days", "1", "-in", f"{name}.csr", "-CA", "ca.crt",
+                "-CAkey", "ca.key", "-CAcreateserial", "-extfile", f"{name}.ext",
+                "-out", f"{name}.crt")
+
+@test
+def tls():
+    with tempfile.TemporaryDirectory() as cert_dir:
+        _generate_certs(cert_dir)
+
+        def path(name):
+            return os.path.join(cert_dir, name)
+
+        with _extra_worker("--listen-address", "localhost:5443",
+                           "--tls-cert-path", path("server.crt"),
+                           "--tls-key-path", path("server.key"),
+                           "--tls-client-ca-path", path("ca.crt")):
+            curl = ["curl", "--silent", "--fail", "--retry", "20", "--retry-connrefused",
+                    "--retry-delay", "1", "--cacert", path("ca.crt"),
+                    "--data", json.dumps({"left": 25, "right": 8}),
+                    "https://localhost:5443/run/multiply"]
+
+            # Clients without a certificate must be rejected
+            result = subprocess.run(curl, capture_output=True, check=False)
+            assert result.returncode != 0
+
+            client_cert = ["--cert", path("client.crt"), "--key", path("client.key")]
+            result = subprocess.run(curl + client_cert, capture_output=True, check=True, text=True)
+            assert_eq(json.loads(result.stdout)["result"], 200)
+
+        config_path = path("config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write(f'''
+listen_address = "localhost:5444"
+
+[tls]
+cert_path = "{path("server.crt")}"
+key_path = "{path("server.key")}"
+handshake_timeout_ms = 500
+''')
+
+        with _extra_worker("--config", config_path):
+            # Clients that never start the handshake must not hold on to the connection
+            for _ in range(100):
+                try:
+                    conn = socket.create_connection(("localhost", 5444))
+                    break
+                except ConnectionRefusedError:
+                    sleep(0.1)
+
+            with conn:
+                conn.settimeout(5)
+                start = time()
+                assert_eq(conn.recv(1), b"")
+                assert time() - start < 4
+
+@test
+def unix_socket():
+    with tempfile.TemporaryDirectory() as sock_dir:
+        sock_path = os.path.join(sock_dir, "ol.sock")
+
+        # Leave a stale socket behind, as a crashed worker would
+        stale = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+        stale.bind(sock_path)
+        stale.close()
+
+        with _extra_worker("--listen-address", f"unix:{sock_path}"):
+            # internal-call also has to reach the worker over the socket
+            result = subprocess.run(["curl", "--silent", "--fail", "--retry", "20",
+                                     "--retry-connrefused", "--retry-delay", "1",
+                                     "--unix-socket", sock_path, "--data", "[]",
+                                     "http://localhost/run/internal-call"],
+                                    capture_output=True, check=False)
+            assert_eq(result.returncode, 0)
+
+def _make_jwt(secret, claims):
+    ''' Creates a JSON web token signed with HS256 '''
+    def encode(data):
+        return base64.urlsafe_b64encode(data).rstrip(b'=').decode()
+
+    header = encode(json.dumps({"alg": "HS256", "typ": "JWT"}).encode())
+    payload = encode(json.dumps(claims).encode())
+    signature = hmac.new(secret, f"{header}.{payload}".encode(), hashlib.sha256).digest()
+
+    return f"{header}.{payload}.{encode(signature)}"
+
+@test
+def authentication():
+    with tempfile.TemporaryDirectory() as auth_dir:
+        secret = b"ol-test-secret"
+        secret_path = os.path.join(auth_dir, "jwt.secret")
+        with open(secret_path, 'wb') as file:
+            file.write(secret)
+
+        config_path = os.path.join(auth_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write(f'''
+listen_address = "localhost:5001"
+
+[[auth.api_keys]]
+name = "multiplier"
+key = "multiply-key"
+functions = ["multiply"]
+
+[[auth.api_keys]]
+name = "deployer"
+key = "deploy-key"
+functions = ["multiply"]
+admin = true
+
+[auth.jwt]
+algorithm = "HS256"
+key_path = "{secret_path}"
+''')
+
+        with _extra_worker("--config", config_path):
+            def call(fn_name, headers):
+                return _post_when_ready(f"http://localhost:5001/run/{fn_name}",
+                                        json={"left": 25, "right": 8}, headers=headers)
+
+            assert_eq(call("multiply", {}).status_code, 401)
+
+            resp = call("multiply", {"X-API-Key": "multiply-key"})
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.json()["result"], 200)
+            assert_eq(call("multiply", {"Authorization": "ApiKey multiply-key"}).status_code, 200)
+
+            # With JWTs configured, bearer tokens are never treated as API keys
+            assert_eq(call("multiply", {"Authorization": "Bearer multiply-key"}).status_code, 401)
+
+            # The key is limited to "multiply"
+            assert_eq(call("echo-request", {"X-API-Key": "multiply-key"}).status_code, 403)
+
+            token = _make_jwt(secret, {"sub": "alice", "functions": ["echo-request"],
+                                       "exp": int(time()) + 60})
+            resp = call("echo-request", {"Authorization": f"Bearer {token}"})
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.json()["identity"], "alice")
+            assert_eq(call("multiply", {"Authorization": f"Bearer {token}"}).status_code, 403)
+
+            # Managing functions requires an admin key with access to the function
+            functions_url = "http://localhost:5001/functions"
+            assert_eq(requests.get(functions_url).status_code, 401)
+            assert_eq(requests.get(functions_url, headers={"X-API-Key": "multiply-key"})
+                      .status_code, 403)
+            assert_eq(requests.get(functions_url, headers={"X-API-Key": "deploy-key"})
+                      .status_code, 200)
+            assert_eq(requests.delete(f"{functions_url}/echo-request",
+                                      headers={"X-API-Key": "deploy-key"}).status_code, 403)
+            assert_eq(requests.put(f"{functions_url}/multiply", data=b"not wasm",
+                                   headers={"X-API-Key": "multiply-key"}).status_code, 403)
+
+            forged = _make_jwt(b"wrong-secret", {"sub": "mallory", "exp": int(time()) + 60})
+            assert_eq(call("echo-request", {"Authorization": f"Bearer {forged}"}).status_code,
+                      401)
+
+@test
+def internal_call_auth():
+    with tempfile.TemporaryDirectory() as tmp_dir:
+        inbox = os.path.join(tmp_dir, "inbox")
+        os.mkdir(inbox)
+
+        config_path = os.path.join(tmp_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write(f'''
+listen_address = "localhost:5015"
+
+[[auth.api_keys]]
+name = "caller"
+key = "caller-key"
+functions = ["internal-call", "noop"]
+
+[[auth.api_keys]]
+name = "limited"
+key = "limited-key"
+functions = ["internal-call"]
+
+[[directory_triggers]]
+function = "internal-call"
+path = "{inbox}"
+args = "contents"
+''')
+
+        with _extra_worker("--config", config_path):
+            url = "http://localhost:5015/run/internal-call"
+
+            # Without JWTs configured, API keys may also be sent as bearer tokens
+            resp = _post_when_ready(url, json={},
+                                    headers={"Authorization": "Bearer caller-key"})
+            assert_eq(resp.status_code, 200)
+
+            # Internal calls are authorized with the credentials of the original client
+            resp = requests.post(url, json={}, headers={"X-API-Key": "limited-key"})
+            assert_eq(resp.status_code, 502)
+            assert "403" in resp.json()["error"], resp.json()
+
+            # Clients cannot skip authentication by claiming to be an internal call
+            resp = requests.post("http://localhost:5015/run/noop", json=[],
+                                 headers={"X-OL-Internal-Call": "forged"})
+            assert_eq(resp.status_code, 401)
+
+            # Calls started by the worker itself have no client credentials to forward
+            tmp_path = os.path.join(inbox, ".call.json")
+            with open(tmp_path, 'w', encoding='utf-8') as file:
+                json.dump({}, file)
+            os.rename(tmp_path, os.path.join(inbox, "call.json"))
+
+            done_path = os.path.join(inbox, "done", "call.json")
+            for _ in range(100):
+                if os.path.exists(done_path):
+                    break
+                sleep(0.1)
+
+        assert os.path.exists(done_path), f"{done_path} does not exist"
+
+@test
+def function_versions():
+    with tempfile.TemporaryDirectory() as tmp_dir:
+        registry = os.path.join(tmp_dir, "registry")
+        os.mkdir(registry)
+        for version in [1, 2]:
+            shutil.copy("./test-registry.wasm/multiply.wasm",
+                        os.path.join(registry, f"multiply@{version}.wasm"))
+
+        def call(name):
+            resp = requests.post(f"http://localhost:5017/run/{name}",
+                                 json={"left": 25, "right": 8}, timeout=10)
+            if resp.status_code != 200:
+                return resp.status_code
+            assert_eq(resp.json()["result"], 200)
+            return int(resp.headers["X-OL-Function-Version"])
+
+        aliases_url = "http://localhost:5017/functions/multiply/aliases"
+
+        with _extra_worker("--listen-address", "localhost:5017", registry=registry):
+            _post_when_ready("http://localhost:5017/run/multiply@1", json={"left": 1, "right": 1})
+
+            # Without a default alias, the latest version is used
+            assert_eq(call("multiply"), 2)
+            assert_eq(call("multiply@1"), 1)
+            assert_eq(call("multiply@3"), 404)
+            assert_eq(call("multiply@prod"), 404)
+
+            resp = requests.put(f"{aliases_url}/prod", json={"1": 1}, timeout=10)
+            assert_eq(resp.status_code, 200)
+            assert_eq(call("multiply@prod"), 1)
+
+            resp = requests.put(f"{aliases_url}/default", json={"1": 1}, timeout=10)
+            assert_eq(resp.status_code, 200)
+            assert_eq(call("multiply"), 1)
+
+            resp = requests.put(f"{aliases_url}/canary", json={"1": 1, "2": 1}, timeout=10)
+            assert_eq(resp.status_code, 200)
+            assert_eq({call("multiply@canary") for _ in range(40)}, {1, 2})
+
+            # Aliases must point to existing versions and cannot look like versions
+            for alias, weights in [("prod", {"3": 1}), ("prod", {"1": 0}), ("prod", {}),
+                                   ("5", {"1": 1})]:
+                resp = requests.put(f"{aliases_url}/{alias}", json=weights, timeout=10)
+                assert_eq(resp.status_code, 400)
+            resp = requests.put(f"{aliases_url}/prod", data=b"not json", timeout=10)
+            assert_eq(resp.status_code, 400)
+            assert_eq(call("multiply@prod"), 1)
+
+            assert_eq(requests.delete(f"{aliases_url}/canary", timeout=10).status_code, 200)
+            assert_eq(requests.delete(f"{aliases_url}/canary", timeout=10).status_code, 404)
+            assert_eq(call("multiply@canary"), 404)
+
+        # Aliases survive restarts
+        with _extra_worker("--listen-address", "localhost:5017", registry=registry):
+            _post_when_ready("http://localhost:5017/run/multiply@1", json={"left": 1, "right": 1})
+
+            resp = requests.get(aliases_url, timeout=10)
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.json(), {"default": {"1": 1}, "prod": {"1": 1}})
+            assert_eq(call("multiply"), 1)
+
+@test
+def function_deployment():
+    with tempfile.TemporaryDirectory() as tmp_dir:
+        registry = os.path.join(tmp_dir, "registry")
+        os.mkdir(registry)
+
+        config_path = os.path.join(tmp_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write('''
+listen_address = "localhost:5013"
+
+[[auth.api_keys]]
+name = "deployer"
+key = "deploy-key"
+functions = ["greeter"]
+admin = true
+''')
+
+        with open("./test-registry.wasm/multiply.wasm", 'rb') as file:
+            code = file.read()
+
+        with _extra_worker("--config", config_path, registry=registry):
+            url = "http://localhost:5013/functions/greeter"
+            headers = {"X-API-Key": "deploy-key"}
+
+            resp = _post_when_ready("http://localhost:5013/run/greeter", headers=headers)
+            assert_eq(resp.status_code, 404)
+
+            assert_eq(requests.put(url, data=code, timeout=10).status_code, 401)
+
+            resp = requests.put(url, data=b"not wasm", headers=headers, timeout=10)
+            assert_eq(resp.status_code, 400)
+            assert_eq(resp.json()["stage"], "deploy")
+
+            # Concurrent uploads of the same function must not clobber each other
+            with ThreadPoolExecutor(max_workers=4) as executor:
+                uploads = [executor.submit(requests.put, url, data=code, headers=headers,
+                                           timeout=30) for _ in range(4)]
+                for upload in uploads:
+                    assert_eq(upload.result().status_code, 200)
+
+            assert_eq(os.listdir(registry), ["greeter.wasm"])
+
+            resp = requests.post("http://localhost:5013/run/greeter", json={"left": 25, "right": 8},
+                                 headers=headers, timeout=10)
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.json()["result"], 200)
+
+            assert_eq(requests.delete(url, headers=headers, timeout=10).status_code, 200)
+            assert_eq(requests.delete(url, headers=headers, timeout=10).status_code, 404)
+            assert_eq(os.listdir(registry), [])
+
+            resp = requests.post("http://localhost:5013/run/greeter", json={"left": 25, "right": 8},
+                                 headers=headers, timeout=10)
+            assert_eq(resp.status_code, 404)
+
+@test
+def rate_limiting():
+    with _extra_worker("--listen-address", "localhost:5002",
+                       "-S", "multiply.rate_limit=0.1", "-S", "multiply.rate_limit_burst=2"):
+        def call():
+            return _post_when_ready("http://localhost:5002/run/multiply",
+                                    json={"left": 25, "right": 8})
+
+        for remaining in [1, 0]:
+            resp = call()
+            assert_eq(resp.status_code, 200)
+            assert_eq(resp.headers["X-RateLimit-Limit"], "2")
+            assert_eq(resp.headers["X-RateLimit-Remaining"], str(remaining))
+
+        resp = call()
+        assert_eq(resp.status_code, 429)
+        assert_eq(resp.json()["stage"], "ratelimit")
+        assert_eq(resp.headers["X-RateLimit-Remaining"], "0")
+        assert int(resp.headers["Retry-After"]) > 0
+
+def _post_from(source_ip, port, path, args):
+    ''' Sends a POST request from a specific local address '''
+    conn = http.client.HTTPConnection("127.0.0.1", port, source_address=(source_ip, 0),
+                                      timeout=10)
+    try:
+        conn.request("POST", path, body=json.dumps(args))
+        return conn.getresponse().status
+    finally:
+        conn.close()
+
+@test
+def client_rate_limits():
+    with tempfile.TemporaryDirectory() as config_dir:
+        config_path = os.path.join(config_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write('''
+listen_address = "localhost:5010"
+
+[rate_limits]
+client_requests_per_sec = 1
+client_burst = 2
+max_clients = 1
+''')
+
+        with _extra_worker("--config", config_path):
+            resp = _post_when_ready("http://localhost:5010/run/multiply",
+                                    json={"left": 25, "right": 8})
+            assert_eq(resp.status_code, 200)
+            assert_eq(_get_status(5010)["num_tracked_clients"], 1)
+
+            # Clients that do not fit into the table anymore share a single limit
+            args = {"left": 25, "right": 8}
+            assert_eq(_post_from("127.0.0.2", 5010, "/run/multiply", args), 200)
+            assert_eq(_post_from("127.0.0.3", 5010, "/run/multiply", args), 200)
+            assert_eq(_post_from("127.0.0.2", 5010, "/run/multiply", args), 429)
+            assert_eq(_get_status(5010)["num_tracked_clients"], 1)
+
+            # Clients stop being tracked by the periodic cleanup once their bucket refilled
+            for _ in range(30):
+                if _get_status(5010)["num_tracked_clients"] == 0:
+                    break
+                sleep(1)
+
+            assert_eq(_get_status(5010)["num_tracked_clients"], 0)
+
+@test
+def trace_propagation():
+    with tempfile.TemporaryDirectory() as trace_dir:
+        span_path = os.path.join(trace_dir, "spans.jsonl")
+        config_path = os.path.join(trace_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write(f'''
+listen_address = "localhost:5003"
+
+[tracing]
+exporter = "jsonl"
+path = "{span_path}"
+''')
+
+        trace_id = "4bf92f3577b34da6a3ce929d0e0e4736"
+        client_span_id = "00f067aa0ba902b7"
+
+        with _extra_worker("--config", config_path):
+            resp = _post_when_ready("http://localhost:5003/run/internal-call", json=[],
+                                    headers={"traceparent": f"00-{trace_id}-{client_span_id}-01"})
+            assert_eq(resp.status_code, 200)
+
+            # Spans are written in the background
+            spans = []
+            for _ in range(50):
+                if os.path.exists(span_path):
+                    with open(span_path, 'r', encoding='utf-8') as file:
+                        spans = [json.loads(line) for line in file]
+                if len(spans) >= 3:
+                    break
+                sleep(0.1)
+
+        spans = {span["name"]: span for span in spans}
+        caller = spans["run internal-call"]
+        call = spans["function_call noop"]
+        callee = spans["run noop"]
+
+        for span in [caller, call, callee]:
+            assert_eq(span["trace_id"], trace_id)
+
+        assert_eq(caller["parent_span_id"], client_span_id)
+        assert_eq(call["parent_span_id"], caller["span_id"])
+        assert_eq(callee["parent_span_id"], call["span_id"])
+
+@test
+def worker_status():
+    resp = requests.get("http://localhost:5000/ready", timeout=10)
+    assert_eq(resp.status_code, 200)
+
+    # Still written for tooling that has not switched to `/ready` yet
+    assert os.path.exists("./ol-wasm.ready")
+
+    resp = requests.get("http://localhost:5000/status", timeout=10)
+    assert_eq(resp.status_code, 200)
+
+    status = resp.json()
+    assert_eq(status["state"], "ready")
+    assert status["uptime_secs"] >= 0
+    assert status["num_functions"] >= 5
+    assert status["cache"]["num_entries"] >= 1
+    assert status["pooling"]["num_instances"] >= status["pooling"]["num_busy_instances"]
+
+@test
+def invocation_logs():
+    resp = requests.post("http://localhost:5000/run/noop?logs=true", json=[], timeout=10)
+    assert_eq(resp.status_code, 200)
+
+    invocation_id = resp.headers["X-OL-Invocation-Id"]
+    lines = json.loads(base64.b64decode(resp.headers["X-OL-Logs"]))
+    assert_eq(len(lines), 1)
+    assert_eq(lines[0]["level"], "info")
+    assert_eq(lines[0]["function"], "noop")
+    assert_eq(lines[0]["invocation_id"], invocation_id)
+    assert_eq(lines[0]["message"], "Hello, world!")
+
+    # Logs are only returned if requested, but always buffered
+    resp = requests.post("http://localhost:5000/run/noop", json=[], timeout=10)
+    assert "X-OL-Logs" not in resp.headers
+    last_invocation_id = resp.headers["X-OL-Invocation-Id"]
+
+    resp = requests.get("http://localhost:5000/logs/noop", timeout=10)
+    assert_eq(resp.status_code, 200)
+    invocation_ids = [line["invocation_id"] for line in resp.json()]
+    assert invocation_id in invocation_ids
+    assert_eq(invocation_ids[-1], last_invocation_id)
+
+    resp = requests.get("http://localhost:5000/logs/does-not-exist", timeout=10)
+    assert_eq(resp.status_code, 404)
+
+    # Logs of all versions are kept together, and can be queried through any of them
+    with tempfile.TemporaryDirectory() as registry:
+        shutil.copy("./test-registry.wasm/noop.wasm", os.path.join(registry, "noop@1.wasm"))
+        shutil.copy("./test-registry.wasm/noop.wasm", os.path.join(registry, "noop@2.wasm"))
+
+        with _extra_worker("--listen-address", "localhost:5016", registry=registry):
+            resp = _post_when_ready("http://localhost:5016/run/noop@1", json=[])
+            assert_eq(resp.status_code, 200)
+            invocation_id = resp.headers["X-OL-Invocation-Id"]
+
+            for name in ["noop", "noop@1", "noop@2"]:
+                resp = requests.get(f"http://localhost:5016/logs/{name}", timeout=10)
+                assert_eq(resp.status_code, 200)
+                assert_eq([line["invocation_id"] for line in resp.json()], [invocation_id])
+
+            resp = requests.get("http://localhost:5016/logs/noop@3", timeout=10)
+            assert_eq(resp.status_code, 404)
+
+@test
+def schedules():
+    with tempfile.TemporaryDirectory() as config_dir:
+        config_path = os.path.join(config_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write('''
+listen_address = "localhost:5004"
+
+[[schedules]]
+name = "multiply-daily"
+function = "multiply"
+cron = "30 4 * * *"
+payload = { left = 25, right = 8 }
+''')
+
+        with _extra_worker("--config", config_path):
+            for _ in range(100):
+                try:
+                    resp = requests.get("http://localhost:5004/schedules", timeout=10)
+                    break
+                except requests.exceptions.ConnectionError:
+                    sleep(0.1)
+
+            assert_eq(resp.status_code, 200)
+            [schedule] = resp.json()
+            assert_eq(schedule["name"], "multiply-daily")
+            assert_eq(schedule["function"], "multiply")
+            assert_eq(schedule["num_runs"], 0)
+            assert_eq(schedule["last_run"], None)
+
+            # Runs are due at 04:30 UTC
+            next_run = schedule["next_run"]
+            assert next_run > time()
+            assert_eq(next_run % 86400, 4 * 3600 + 30 * 60)
+
+@test
+def scheduled_runs():
+    with tempfile.TemporaryDirectory() as config_dir:
+        config_path = os.path.join(config_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write('''
+listen_address = "localhost:5006"
+
+[[schedules]]
+name = "busy-every-minute"
+function = "busy-wait"
+cron = "* * * * *"
+payload = { secs = 70 }
+''')
+
+        with _extra_worker("--config", config_path):
+            # The first run starts within a minute and is still running when the next one is due
+            schedule = None
+            for _ in range(180):
+                try:
+                    resp = requests.get("http://localhost:5006/schedules", timeout=10)
+                    [schedule] = resp.json()
+                    if schedule["last_run"] is not None:
+                        break
+                except requests.exceptions.ConnectionError:
+                    pass
+                sleep(1)
+
+            assert schedule is not None and schedule["last_run"] is not None
+            assert_eq(schedule["num_runs"], 1)
+            assert_eq(schedule["num_skipped"], 1)
+            assert_eq(schedule["last_run"]["status"], 200)
+            assert schedule["last_run"]["duration_ms"] >= 60000
+
+@test
+def directory_trigger():
+    with tempfile.TemporaryDirectory() as tmp_dir:
+        inbox = os.path.join(tmp_dir, "inbox")
+        os.mkdir(inbox)
+
+        # Files that exist on startup are processed as well
+        with open(os.path.join(inbox, "good.json"), 'w', encoding='utf-8') as file:
+            json.dump({"left": 25, "right": 8}, file)
+
+        config_path = os.path.join(tmp_dir, "config.toml")
+        with open(config_path, 'w', encoding='utf-8') as file:
+            file.write(f'''
+listen_address = "localhost:5005"
+
+[[directory_triggers]]
+function = "multiply"
+path = "{inbox}"
+args = "contents"
+''')
+
+        with _extra_worker("--config", config_path):
+            _post_when_ready("http://localhost:5005/run/noop", json=[])
+
+            # Write under a hidden name first, so the file is only picked up once complete
+            tmp_path = os.path.join(inbox, ".bad.json")
+            with open(tmp_path, 'w', encoding='utf-8') as file:
+                file.write("not json")
+            os.rename(tmp_path, os.path.join(inbox, "bad.json"))
+
+            expected = [os.path.join(inbox, "done", "good.json"),
+                        os.path.join(inbox, "failed", "bad.json")]
+            for _ in range(100):
+                if all(os.path.exists(path) for path in expected):
+                    break
+                sleep(0.1)
+
+        for path in expected:
+            assert os.path.exists(path), f"{path} does not exist"
+        assert_eq(sorted(os.listdir(inbox)), ["done", "failed"])
+
+def run_tests(wasm):
     ''' Runs all tests '''
 
     ping()
@@ -66,6 +978,35 @@ def run_tests():
     internal_call()
     multiply()
 
+    if wasm:
+        stats()
+        request_metadata()
+        custom_status()
+        failed_internal_call()
+        timeouts()
+        fuel_limits()
+        memory_limits()
+        graceful_shutdown()
+        call_queueing()
+        hot_reload()
+        async_invocation()
+        invocation_expiry()
+        http_protocols()
+        tls()
+        unix_socket()
+        authentication()
+        internal_call_auth()
+        function_deployment()
+        function_versions()
+        rate_limiting()
+        client_rate_limits()
+        trace_propagation()
+        invocation_logs()
+        worker_status()
+        schedules()
+        scheduled_runs()
+        directory_trigger()
+
 def _main():
     parser = argparse.ArgumentParser(description='Run tests for OpenLambda')
     parser.add_argument('--test_filter', type=str, default="")
@@ -90,7 +1031,7 @@ def _main():
 
     if wasm:
         start_tests()
-        run_tests()
+        run_tests(wasm)
     else:
         setup_config(args.ol_dir)
         prepare_open_lambda(args.ol_dir)
@@ -99,7 +1040,7 @@ def _main():
 
         registry = os.path.abspath(args.registry)
         with TestConfContext(registry=registry):
-            run_tests()
+            run_tests(wasm)
 
     check_test_results()
 
@@ -6,8 +6,6 @@ from subprocess import check_output, Popen
 from time import sleep
 from collections import OrderedDict
 from sys import stdout
-from os.path import exists
-from os import remove
 
 import copy
 import subprocess
@@ -220,18 +218,26 @@ class WasmWorker():
         stdout.write("Starting WebAssembly worker.")
         stdout.flush()
 
-        if exists('./ol-wasm.ready'):
-            remove('./ol-wasm.ready')
-
         self._process = Popen(["./ol-wasm"])
 
-        while not exists('./ol-wasm.ready'):
+        while not self._is_ready():
+            if self._process.poll() is not None:
+                raise RuntimeError("WebAssembly worker exited during startup")
+
             sleep(0.5)
             stdout.write('.')
             stdout.flush()
 
         print("Done")
 
+    @staticmethod
+    def _is_ready():
+        ''' Has the worker loaded all functions? '''
+        try:
+            return requests.get("http://localhost:5000/ready", timeout=1).status_code == 200
+        except requests.exceptions.ConnectionError:
+            return False
+
     def __del__(self):
         self.stop()
 
@@ -252,6 +258,8 @@ class WasmWorker():
 
         print("Stopping WebAssembly worker")
         self._process.terminate()
+        # Wait for running calls to drain
+        self._process.wait()
         self._process = None
 
 def prepare_open_lambda(ol_dir):
@@ -142,6 +142,8 @@ func (linst *LambdaInstance) Task() {
 			if err != nil {
 				linst.TrySendError(req, http.StatusInternalServerError, "Could not create NewRequest: "+err.Error(), sb)
 			} else {
+				// the runtime passes the request headers on to the function
+				httpReq.Header = req.r.Header.Clone()
 				resp, err := sb.Client().Do(httpReq)
 
 				// copy response out
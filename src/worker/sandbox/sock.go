@@ -115,6 +115,8 @@ func (container *SOCKContainer) launchContainerProxy() (err error) {
 	args := []string{}
 	args = append(args, "ol-container-proxy")
 	args = append(args, container.scratchDir)
+	// The proxy can also take an address to serve metrics at, but each container
+	// would need its own port, so it is not enabled here
 
 	var procAttr os.ProcAttr
 	procAttr.Files = []*os.File{os.Stdin, os.Stdout, os.Stderr}
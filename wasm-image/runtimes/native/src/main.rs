@@ -21,8 +21,52 @@ use tokio_stream::wrappers::UnixListenerStream;
 use nix::sched::{unshare, CloneFlags};
 use nix::unistd::{fork, getpid, ForkResult};
 
+use serde::Deserialize;
+
 use std::os::unix::net::UnixListener as StdUnixListener;
 
+/// Status code and headers set by the function (see `set_status` and `set_header` in the bindings)
+const RESULT_META_PATH: &str = "/tmp/output.meta";
+
+/// The request as seen by the function (see `request.rs` in the standalone bindings)
+struct RequestMeta {
+    method: Method,
+    /// The path after the function name
+    path: String,
+    query: String,
+    headers: Vec<(String, String)>,
+}
+
+impl RequestMeta {
+    fn new<T>(req: &Request<T>) -> Self {
+        // Requests are forwarded as `/run/<function>/<path>`
+        let path = match req.uri().path().strip_prefix("/run/") {
+            Some(rest) => rest.find('/').map(|idx| rest[idx..].to_string()),
+            None => None,
+        };
+
+        let headers = req
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+
+        Self {
+            method: req.method().clone(),
+            path: path.unwrap_or_default(),
+            query: req.uri().query().unwrap_or_default().to_string(),
+            headers,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+struct ResultMeta {
+    status: Option<u16>,
+    #[serde(default)]
+    headers: Vec<(String, String)>,
+}
+
 // Taken from: https://github.com/hyperium/hyper/blob/master/examples/single_threaded.rs
 #[derive(Clone, Copy, Debug)]
 struct LocalExec;
@@ -43,7 +87,12 @@ fn main() {
             log::trace!("Got new request: {req:?}");
 
             let mut args = Vec::new();
-            let method = req.method().clone();
+            let request = RequestMeta::new(&req);
+            let traceparent = req
+                .headers()
+                .get("traceparent")
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
 
             let mut body = req.into_body();
 
@@ -57,11 +106,7 @@ fn main() {
                 }
             }
 
-            if method == &Method::POST {
-                execute_function(args).await
-            } else {
-                panic!("Got unexpected request");
-            }
+            execute_function(args, request, traceparent).await
         }))
     });
 
@@ -127,17 +172,42 @@ fn main() {
     });
 }
 
-async fn execute_function(args: Vec<u8>) -> Result<Response<Body>> {
+async fn execute_function(
+    args: Vec<u8>,
+    request: RequestMeta,
+    traceparent: Option<String>,
+) -> Result<Response<Body>> {
     use std::io::Read;
 
     let arg_str = String::from_utf8(args).unwrap();
     log::debug!("Executing function with arg `{arg_str}`");
 
     let body;
-    let status_code;
+    let mut status_code;
+    let mut meta = ResultMeta::default();
 
-    let mut child = Command::new("/handler/f.bin")
+    // Do not apply the status and headers of a previous call
+    if let Err(err) = std::fs::remove_file(RESULT_META_PATH) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            log::warn!("Failed to remove {RESULT_META_PATH}: {err}");
+        }
+    }
+
+    let mut command = Command::new("/handler/f.bin");
+
+    // Forwarded by the bindings on function calls
+    if let Some(traceparent) = traceparent.as_deref().and_then(child_traceparent) {
+        command.env("TRACEPARENT", traceparent);
+    }
+
+    let headers = serde_json::to_string(&request.headers).expect("Failed to serialize headers");
+
+    let mut child = command
         .arg(arg_str)
+        .env("OL_REQUEST_METHOD", request.method.as_str())
+        .env("OL_REQUEST_PATH", request.path)
+        .env("OL_REQUEST_QUERY", request.query)
+        .env("OL_REQUEST_HEADERS", headers)
         .env("RUST_LOG", "debug")
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
@@ -208,6 +278,24 @@ async fn execute_function(args: Vec<u8>) -> Result<Response<Body>> {
         }
     }
 
+    if status_code == StatusCode::OK {
+        match std::fs::read_to_string(RESULT_META_PATH) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(m) => meta = m,
+                Err(err) => log::error!("Invalid result metadata: {err}"),
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => log::error!("Failed to read result metadata: {err}"),
+        }
+
+        if let Some(status) = meta.status {
+            match StatusCode::from_u16(status) {
+                Ok(status) => status_code = status,
+                Err(_) => log::error!("Function set invalid status code {status}"),
+            }
+        }
+    }
+
     let mut stdout = String::from("");
     let mut stderr = String::from("");
 
@@ -250,7 +338,60 @@ async fn execute_function(args: Vec<u8>) -> Result<Response<Body>> {
         log::trace!("{}", log_line);
     }
 
-    let response = Response::builder().status(status_code).body(body).unwrap();
+    let mut response = Response::builder().status(status_code);
+    for (name, value) in meta.headers {
+        response = response.header(name, value);
+    }
+
+    let response = match response.body(body) {
+        Ok(response) => response,
+        Err(err) => {
+            let err_str = format!("Function set invalid response header: {err}");
+            log::error!("{err_str}");
+
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from(err_str))
+                .unwrap()
+        }
+    };
 
     Ok(response)
 }
+
+/// Starts a span for this invocation within the caller's trace
+///
+/// Returns `None` if the `traceparent` is invalid.
+fn child_traceparent(traceparent: &str) -> Option<String> {
+    let is_hex = |value: &str, len: usize| {
+        value.len() == len && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+    };
+
+    let parts: Vec<&str> = traceparent.trim().split('-').collect();
+    let [version, trace_id, parent_id, flags] = parts.as_slice() else {
+        return None;
+    };
+
+    if !is_hex(version, 2) || *version == "ff" || !is_hex(trace_id, 32) {
+        return None;
+    }
+    if !is_hex(parent_id, 16) || !is_hex(flags, 2) {
+        return None;
+    }
+
+    let span_id = new_span_id()?;
+    Some(format!("00-{trace_id}-{span_id:016x}-{flags}"))
+}
+
+fn new_span_id() -> Option<u64> {
+    use std::io::Read;
+
+    let mut bytes = [0u8; 8];
+    if let Err(err) = File::open("/dev/urandom").and_then(|mut f| f.read_exact(&mut bytes)) {
+        log::error!("Failed to generate span ID: {err}");
+        return None;
+    }
+
+    // All-zero IDs are invalid
+    Some(u64::from_ne_bytes(bytes).max(1))
+}
@@ -0,0 +1,46 @@
+use std::fmt;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Prefix of listen addresses that refer to a UNIX domain socket
+const UNIX_PREFIX: &str = "unix:";
+
+/// Where the worker accepts requests
+#[derive(Clone, Debug)]
+pub enum WorkerAddr {
+    Tcp(SocketAddr),
+    Unix(Arc<PathBuf>),
+}
+
+impl WorkerAddr {
+    /// Parses either `<host>:<port>` or `unix:<path>`
+    pub fn parse(address: &str) -> anyhow::Result<Self> {
+        if let Some(path) = address.strip_prefix(UNIX_PREFIX) {
+            if path.is_empty() {
+                anyhow::bail!("Invalid listen address \"{address}\"; socket path is empty");
+            }
+
+            return Ok(Self::Unix(Arc::new(path.into())));
+        }
+
+        match address.to_socket_addrs() {
+            Ok(mut addrs) => match addrs.next() {
+                Some(addr) => Ok(Self::Tcp(addr)),
+                None => {
+                    anyhow::bail!("Listen address \"{address}\" did not resolve to any address")
+                }
+            },
+            Err(err) => anyhow::bail!("Failed to parse listen address \"{address}\": {err}"),
+        }
+    }
+}
+
+impl fmt::Display for WorkerAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{addr}"),
+            Self::Unix(path) => write!(f, "{UNIX_PREFIX}{}", path.display()),
+        }
+    }
+}
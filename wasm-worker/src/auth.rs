@@ -0,0 +1,436 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use http_body_util::Full;
+
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap, HeaderValue};
+use hyper::{http, Response, StatusCode};
+
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{ErrorResponse, ErrorStage};
+
+/// Alternative to `Authorization: ApiKey <key>` for API keys
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Grants access to all functions
+const ANY_FUNCTION: &str = "*";
+
+/// Which clients may invoke functions
+///
+/// Authentication is disabled unless at least one API key or a JWT key is configured.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub jwt: Option<JwtConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Identifies the client to guests and in the logs
+    pub name: String,
+    pub key: String,
+    /// The functions this key may invoke ('*' for all of them)
+    #[serde(default = "all_functions")]
+    pub functions: Vec<String>,
+    /// Whether this key may deploy and remove functions (and manage their aliases)
+    #[serde(default)]
+    pub admin: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtConfig {
+    pub algorithm: JwtAlgorithm,
+    /// The shared secret (HS256) or PEM-encoded public key (RS256)
+    pub key_path: PathBuf,
+    /// If set, tokens must have a matching "iss" claim
+    pub issuer: Option<String>,
+    /// If set, tokens must have a matching "aud" claim
+    pub audience: Option<String>,
+    /// The claim listing the functions a token may invoke
+    ///
+    /// Tokens without this claim may invoke all functions.
+    #[serde(default = "default_functions_claim")]
+    pub functions_claim: String,
+    /// The boolean claim that allows a token to manage functions
+    #[serde(default = "default_admin_claim")]
+    pub admin_claim: String,
+    /// Tolerated clock skew when checking "exp" and "nbf"
+    #[serde(default = "default_leeway_secs")]
+    pub leeway_secs: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+}
+
+fn all_functions() -> Vec<String> {
+    vec![ANY_FUNCTION.to_string()]
+}
+
+fn default_functions_claim() -> String {
+    "functions".to_string()
+}
+
+fn default_admin_claim() -> String {
+    "admin".to_string()
+}
+
+fn default_leeway_secs() -> u64 {
+    60
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    ApiKey,
+    Jwt,
+}
+
+/// A verified client, as seen by guests
+#[derive(Debug, Serialize)]
+pub struct Identity {
+    pub method: AuthMethod,
+    /// The name of the API key or the "sub" claim of the token
+    pub subject: String,
+    /// The functions this client may invoke (`None` if unrestricted)
+    pub functions: Option<Vec<String>>,
+    /// Whether this client may manage functions
+    pub admin: bool,
+    /// All claims of the token (empty for API keys)
+    pub claims: serde_json::Value,
+}
+
+impl Identity {
+    pub fn may_invoke(&self, function: &str) -> bool {
+        // Access to a function includes all of its versions and aliases
+        let function = match function.split_once('@') {
+            Some((name, _)) => name,
+            None => function,
+        };
+
+        match &self.functions {
+            Some(functions) => functions
+                .iter()
+                .any(|allowed| allowed == ANY_FUNCTION || allowed == function),
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// The request did not contain any credentials
+    Missing,
+    /// The credentials could not be verified
+    Invalid(String),
+    /// The client may not invoke this function
+    Forbidden { subject: String, function: String },
+    /// The client may not manage functions
+    NotAdmin { subject: String },
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "Missing credentials"),
+            Self::Invalid(msg) => write!(f, "Invalid credentials: {msg}"),
+            Self::Forbidden { subject, function } => {
+                write!(f, "\"{subject}\" may not invoke function \"{function}\"")
+            }
+            Self::NotAdmin { subject } => write!(f, "\"{subject}\" may not manage functions"),
+        }
+    }
+}
+
+impl AuthError {
+    pub fn into_response(self) -> http::Result<Response<Full<Bytes>>> {
+        let status = match self {
+            Self::Missing | Self::Invalid(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden { .. } | Self::NotAdmin { .. } => StatusCode::FORBIDDEN,
+        };
+
+        let mut response = ErrorResponse::new(ErrorStage::Auth, &self).into_response(status)?;
+
+        if status == StatusCode::UNAUTHORIZED {
+            response
+                .headers_mut()
+                .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+
+        Ok(response)
+    }
+}
+
+struct JwtVerifier {
+    key: DecodingKey,
+    validation: Validation,
+    functions_claim: String,
+    admin_claim: String,
+}
+
+/// Verifies the credentials of clients
+pub struct Authenticator {
+    api_keys: Vec<ApiKeyConfig>,
+    jwt: Option<JwtVerifier>,
+}
+
+impl Authenticator {
+    /// Sets up authentication, or returns `None` if it is disabled
+    pub fn new(config: AuthConfig) -> anyhow::Result<Option<Self>> {
+        if config.api_keys.is_empty() && config.jwt.is_none() {
+            return Ok(None);
+        }
+
+        for api_key in config.api_keys.iter() {
+            if api_key.key.is_empty() {
+                anyhow::bail!("API key \"{}\" is empty", api_key.name);
+            }
+        }
+
+        let jwt = match config.jwt {
+            Some(jwt_config) => Some(JwtVerifier::new(jwt_config)?),
+            None => None,
+        };
+
+        Ok(Some(Self {
+            api_keys: config.api_keys,
+            jwt,
+        }))
+    }
+
+    /// Checks the credentials of a request and whether they grant access to a function
+    pub fn authorize(&self, headers: &HeaderMap, function: &str) -> Result<Identity, AuthError> {
+        let identity = self.authenticate(headers)?;
+
+        if identity.may_invoke(function) {
+            Ok(identity)
+        } else {
+            Err(AuthError::Forbidden {
+                subject: identity.subject,
+                function: function.to_string(),
+            })
+        }
+    }
+
+    /// Checks that a request may deploy, remove, or list functions
+    ///
+    /// Changes to a single function additionally require access to that function.
+    pub fn authorize_admin(
+        &self,
+        headers: &HeaderMap,
+        function: Option<&str>,
+    ) -> Result<Identity, AuthError> {
+        let identity = self.authenticate(headers)?;
+
+        if !identity.admin {
+            return Err(AuthError::NotAdmin {
+                subject: identity.subject,
+            });
+        }
+
+        match function {
+            Some(function) if !identity.may_invoke(function) => Err(AuthError::Forbidden {
+                subject: identity.subject,
+                function: function.to_string(),
+            }),
+            _ => Ok(identity),
+        }
+    }
+
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Identity, AuthError> {
+        match get_credentials(headers)? {
+            Some(Credentials::ApiKey(key)) => self.verify_api_key(key),
+            // Bearer tokens are only API keys if JWTs are not accepted at all
+            Some(Credentials::Bearer(token)) => match &self.jwt {
+                Some(jwt) => jwt.verify(token),
+                None => self.verify_api_key(token),
+            },
+            None => Err(AuthError::Missing),
+        }
+    }
+
+    fn verify_api_key(&self, token: &str) -> Result<Identity, AuthError> {
+        // Check all keys, so that the time it takes does not reveal which one matched
+        let mut found = None;
+        for api_key in self.api_keys.iter() {
+            if constant_time_eq(api_key.key.as_bytes(), token.as_bytes()) {
+                found = Some(api_key);
+            }
+        }
+
+        match found {
+            Some(api_key) => Ok(Identity {
+                method: AuthMethod::ApiKey,
+                subject: api_key.name.clone(),
+                functions: Some(api_key.functions.clone()),
+                admin: api_key.admin,
+                claims: serde_json::Value::Object(Default::default()),
+            }),
+            None => Err(AuthError::Invalid("Unknown API key".to_string())),
+        }
+    }
+}
+
+impl JwtVerifier {
+    fn new(config: JwtConfig) -> anyhow::Result<Self> {
+        let key_data = std::fs::read(&config.key_path)
+            .with_context(|| format!("Failed to read JWT key at {:?}", config.key_path))?;
+
+        let (algorithm, key) = match config.algorithm {
+            JwtAlgorithm::HS256 => {
+                let secret = key_data.strip_suffix(b"\n").unwrap_or(&key_data);
+                let secret = secret.strip_suffix(b"\r").unwrap_or(secret);
+                if secret.is_empty() {
+                    anyhow::bail!("JWT secret at {:?} is empty", config.key_path);
+                }
+
+                (Algorithm::HS256, DecodingKey::from_secret(secret))
+            }
+            JwtAlgorithm::RS256 => (
+                Algorithm::RS256,
+                DecodingKey::from_rsa_pem(&key_data).with_context(|| {
+                    format!("Failed to parse RSA public key at {:?}", config.key_path)
+                })?,
+            ),
+        };
+
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = config.leeway_secs;
+
+        if let Some(issuer) = &config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        match &config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        Ok(Self {
+            key,
+            validation,
+            functions_claim: config.functions_claim,
+            admin_claim: config.admin_claim,
+        })
+    }
+
+    fn verify(&self, token: &str) -> Result<Identity, AuthError> {
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &self.key, &self.validation)
+            .map_err(|err| AuthError::Invalid(err.to_string()))?
+            .claims;
+
+        let subject = claims
+            .get("sub")
+            .and_then(|sub| sub.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        // Accept both a list and a space-separated string (like OAuth scopes)
+        let functions = match claims.get(&self.functions_claim) {
+            None => None,
+            Some(serde_json::Value::String(functions)) => {
+                Some(functions.split_whitespace().map(str::to_string).collect())
+            }
+            Some(serde_json::Value::Array(functions)) => Some(
+                functions
+                    .iter()
+                    .map(|function| {
+                        function.as_str().map(str::to_string).ok_or_else(|| {
+                            AuthError::Invalid(format!(
+                                "Claim \"{}\" must only contain strings",
+                                self.functions_claim
+                            ))
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Some(_) => {
+                return Err(AuthError::Invalid(format!(
+                    "Claim \"{}\" must be a string or a list",
+                    self.functions_claim
+                )));
+            }
+        };
+
+        let admin = match claims.get(&self.admin_claim) {
+            None => false,
+            Some(serde_json::Value::Bool(admin)) => *admin,
+            Some(_) => {
+                return Err(AuthError::Invalid(format!(
+                    "Claim \"{}\" must be a boolean",
+                    self.admin_claim
+                )));
+            }
+        };
+
+        Ok(Identity {
+            method: AuthMethod::Jwt,
+            subject,
+            functions,
+            admin,
+            claims,
+        })
+    }
+}
+
+/// The credentials of a request, by how they were sent
+enum Credentials<'a> {
+    /// `X-API-Key: <key>` or `Authorization: ApiKey <key>`
+    ApiKey(&'a str),
+    /// `Authorization: Bearer <token>`
+    Bearer(&'a str),
+}
+
+/// Does the request contain any credentials (valid or not)?
+pub fn has_credentials(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::AUTHORIZATION) || headers.contains_key(API_KEY_HEADER)
+}
+
+/// Extracts the token from the "Authorization" or "X-API-Key" header
+fn get_credentials(headers: &HeaderMap) -> Result<Option<Credentials<'_>>, AuthError> {
+    fn to_str(value: &HeaderValue) -> Result<&str, AuthError> {
+        value
+            .to_str()
+            .map_err(|_| AuthError::Invalid("Credentials are not valid ASCII".to_string()))
+    }
+
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        let value = to_str(value)?;
+
+        return match value.split_once(' ') {
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                Ok(Some(Credentials::Bearer(token.trim())))
+            }
+            Some((scheme, key)) if scheme.eq_ignore_ascii_case("apikey") => {
+                Ok(Some(Credentials::ApiKey(key.trim())))
+            }
+            _ => Err(AuthError::Invalid(
+                "Expected \"Authorization: Bearer <token>\" or \"Authorization: ApiKey <key>\""
+                    .to_string(),
+            )),
+        };
+    }
+
+    match headers.get(API_KEY_HEADER) {
+        Some(value) => Ok(Some(Credentials::ApiKey(to_str(value)?.trim()))),
+        None => Ok(None),
+    }
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,16 +1,56 @@
+use std::fmt;
 use std::future::Future;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use hyper::header::{self, HeaderName, HeaderValue};
+use hyper::StatusCode;
+
 use parking_lot::Mutex;
 
 use rand::Fill;
 
 use wasmtime::{Caller, Linker, Val};
 
-use super::{fill_slice, get_slice, get_slice_mut, set_u64, BindingsData};
+use super::{fill_slice, get_slice, get_slice_mut, get_str, set_u64, BindingsData};
+
+use crate::logs::InvocationLogs;
+
+/// Everything a guest hands back to the caller
+#[derive(Debug, Default)]
+pub struct FunctionResult {
+    pub body: Option<Vec<u8>>,
+    pub status: Option<StatusCode>,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    /// Messages the guest logged during the call
+    pub logs: InvocationLogs,
+}
+
+pub type ResultHandle = Arc<Mutex<FunctionResult>>;
+
+/// The guest handed over an invalid result
+#[derive(Debug)]
+pub enum ResultError {
+    AlreadySet,
+    InvalidStatus(u32),
+    InvalidHeader(String),
+    ReservedHeader(String),
+}
+
+impl fmt::Display for ResultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadySet => write!(f, "Result was already set"),
+            Self::InvalidStatus(status) => write!(f, "Invalid status code {status}"),
+            Self::InvalidHeader(name) => write!(f, "Invalid response header \"{name}\""),
+            Self::ReservedHeader(name) => {
+                write!(f, "Response header \"{name}\" is managed by the worker")
+            }
+        }
+    }
+}
 
-pub type ResultHandle = Arc<Mutex<Option<Vec<u8>>>>;
+impl std::error::Error for ResultError {}
 
 pub struct ArgsData {
     args: Vec<u8>,
@@ -29,6 +69,10 @@ impl ArgsData {
     pub fn set_result_handle(&mut self, new_hdl: ResultHandle) {
         self.result = new_hdl;
     }
+
+    pub fn get_result_handle(&self) -> &ResultHandle {
+        &self.result
+    }
 }
 
 fn get_args(
@@ -74,7 +118,11 @@ fn get_unix_time() -> u64 {
         .as_secs()
 }
 
-fn set_result(mut caller: Caller<'_, BindingsData>, buf_ptr: i32, buf_len: u32) {
+fn set_result(
+    mut caller: Caller<'_, BindingsData>,
+    buf_ptr: i32,
+    buf_len: u32,
+) -> anyhow::Result<()> {
     log::trace!("Got \"set_result\" call with result of size {buf_len}");
 
     let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
@@ -82,8 +130,8 @@ fn set_result(mut caller: Caller<'_, BindingsData>, buf_ptr: i32, buf_len: u32)
     let data = &caller.data().args;
     let mut result = data.result.lock();
 
-    if result.is_some() {
-        panic!("Result was already set");
+    if result.body.is_some() {
+        return Err(ResultError::AlreadySet.into());
     }
 
     let buf_slice = get_slice(&caller, &memory, buf_ptr, buf_len);
@@ -91,7 +139,51 @@ fn set_result(mut caller: Caller<'_, BindingsData>, buf_ptr: i32, buf_len: u32)
     let mut vec = Vec::new();
     vec.extend_from_slice(buf_slice);
 
-    *result = Some(vec);
+    result.body = Some(vec);
+    Ok(())
+}
+
+fn set_status(caller: Caller<'_, BindingsData>, status: u32) -> anyhow::Result<()> {
+    log::trace!("Got \"set_status\" call with status {status}");
+
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .ok_or(ResultError::InvalidStatus(status))?;
+
+    caller.data().args.result.lock().status = Some(status);
+    Ok(())
+}
+
+fn set_header(
+    mut caller: Caller<'_, BindingsData>,
+    name_ptr: i32,
+    name_len: u32,
+    value_ptr: i32,
+    value_len: u32,
+) -> anyhow::Result<()> {
+    let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+
+    let name = get_str(&caller, &memory, name_ptr, name_len);
+    let value = get_slice(&caller, &memory, value_ptr, value_len);
+
+    log::trace!("Got \"set_header\" call for header \"{name}\"");
+
+    let name = HeaderName::from_bytes(name.as_bytes())
+        .map_err(|_| ResultError::InvalidHeader(name.to_string()))?;
+    let value =
+        HeaderValue::from_bytes(value).map_err(|_| ResultError::InvalidHeader(name.to_string()))?;
+
+    // These would break the framing of the response
+    if name == header::CONTENT_LENGTH
+        || name == header::TRANSFER_ENCODING
+        || name == header::CONNECTION
+    {
+        return Err(ResultError::ReservedHeader(name.to_string()).into());
+    }
+
+    caller.data().args.result.lock().headers.push((name, value));
+    Ok(())
 }
 
 fn get_random_value(mut caller: Caller<'_, BindingsData>, buf_ptr: i32, buf_len: u32) {
@@ -114,6 +206,8 @@ pub fn get_imports(linker: &mut Linker<BindingsData>) {
         .func_wrap(module, "get_unix_time", get_unix_time)
         .unwrap();
     linker.func_wrap(module, "set_result", set_result).unwrap();
+    linker.func_wrap(module, "set_status", set_status).unwrap();
+    linker.func_wrap(module, "set_header", set_header).unwrap();
     linker
         .func_wrap1_async(module, "get_args", get_args)
         .unwrap();
@@ -1,5 +1,6 @@
 use std::future::Future;
-use std::net::SocketAddr;
+
+use hyper::header::{self, HeaderMap, HeaderName, HeaderValue};
 
 use serde_bytes::ByteBuf;
 
@@ -9,15 +10,20 @@ use super::{call_allocate, fill_slice, get_slice, get_str, set_u64, BindingsData
 
 use wasmtime::{Caller, Linker};
 
+use crate::address::WorkerAddr;
+use crate::auth::API_KEY_HEADER;
 use crate::http_client::HttpClient;
+use crate::metrics;
+use crate::ratelimit::{internal_call_token, INTERNAL_CALL_HEADER};
+use crate::trace::{Span, SpanKind, TRACEPARENT_HEADER};
 
 #[derive(Clone)]
 pub struct IpcData {
-    addr: SocketAddr,
+    addr: WorkerAddr,
 }
 
 impl IpcData {
-    pub fn new(addr: SocketAddr) -> Self {
+    pub fn new(addr: WorkerAddr) -> Self {
         Self { addr }
     }
 }
@@ -32,25 +38,62 @@ fn function_call(
 ) -> Box<dyn Future<Output = i64> + Send + '_> {
     Box::new(async move {
         log::trace!("Got `function_call` call");
+        metrics::HOST_CALLS
+            .with_label_values(&["function_call"])
+            .inc();
 
         let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
         let func_name = get_str(&caller, &memory, func_name_ptr, func_name_len);
 
         let args = get_slice(&caller, &memory, arg_data_ptr, arg_data_len);
 
-        let mut client = HttpClient::new(caller.data().ipc.addr).await;
+        // Internal calls are made on behalf of the original client
+        let mut headers = HeaderMap::new();
+        let request_headers = &caller.data().request.get_request().headers;
+        for name in [
+            header::AUTHORIZATION,
+            HeaderName::from_static(API_KEY_HEADER),
+        ] {
+            if let Some(value) = request_headers.get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        headers.insert(
+            INTERNAL_CALL_HEADER,
+            HeaderValue::from_static(internal_call_token()),
+        );
+
+        let mut span = start_call_span(&caller, format!("function_call {func_name}"), &mut headers);
+
+        let response = match HttpClient::connect(&caller.data().ipc.addr).await {
+            Ok(mut client) => client
+                .post_with_status(format!("/run/{func_name}"), headers, args.to_vec())
+                .await
+                .map_err(anyhow::Error::from),
+            Err(err) => Err(err),
+        };
 
-        let response = match client
-            .post(format!("/run/{func_name}"), args.to_vec())
-            .await
-        {
-            Ok(resp) => resp,
+        // Errors of the callee are passed on to the guest along with its status code
+        let result: CallResult = match response {
+            Ok((status, body)) => {
+                span.set_attribute("http.status_code", status.as_u16());
+
+                if status.is_success() {
+                    Ok(ByteBuf::from(body))
+                } else {
+                    Err(format!(
+                        "Call to \"{func_name}\" failed with status {status}: {}",
+                        String::from_utf8_lossy(&body)
+                    ))
+                }
+            }
             Err(err) => {
-                panic!("Internal call to {} failed: {err}", caller.data().ipc.addr);
+                log::warn!("Internal call to \"{func_name}\" failed: {err:#}");
+                Err(format!("Call to \"{func_name}\" failed: {err:#}"))
             }
         };
 
-        let result: CallResult = Ok(ByteBuf::from(response));
+        span.end(result.is_err());
 
         let result_data = bincode::serialize(&result).unwrap();
         let buffer_len = result_data.len();
@@ -81,15 +124,25 @@ fn http_post(
         let path = get_str(&caller, &memory, path_ptr, path_len);
 
         log::trace!("Got `http_post` call to {addr} with path={path}");
+        metrics::HOST_CALLS.with_label_values(&["http_post"]).inc();
 
         let body_slice = get_slice(&caller, &memory, body_data_ptr, body_data_len);
 
+        let mut headers = HeaderMap::new();
+        let mut span = start_call_span(&caller, "http_post".to_string(), &mut headers);
+        span.set_attribute("http.url", format!("{addr}{path}"));
+
         let mut client = HttpClient::new(addr).await;
-        let result: CallResult = match client.post(path.to_string(), body_slice.to_vec()).await {
+        let result: CallResult = match client
+            .post(path.to_string(), headers, body_slice.to_vec())
+            .await
+        {
             Ok(data) => Ok(ByteBuf::from(data)),
             Err(err) => Err(err.to_string()),
         };
 
+        span.end(result.is_err());
+
         let result_data = bincode::serialize(&result).unwrap();
         let buffer_len = result_data.len();
 
@@ -116,14 +169,21 @@ fn http_get(
         let addr = get_str(&caller, &memory, addr_ptr, addr_len);
         let path = get_str(&caller, &memory, path_ptr, path_len);
 
-        log::trace!("Got `http_post` call to {addr} with path={path}");
+        log::trace!("Got `http_get` call to {addr} with path={path}");
+        metrics::HOST_CALLS.with_label_values(&["http_get"]).inc();
+
+        let mut headers = HeaderMap::new();
+        let mut span = start_call_span(&caller, "http_get".to_string(), &mut headers);
+        span.set_attribute("http.url", format!("{addr}{path}"));
 
         let mut client = HttpClient::new(addr).await;
-        let result: CallResult = match client.get(path.to_string()).await {
+        let result: CallResult = match client.get(path.to_string(), headers).await {
             Ok(data) => Ok(ByteBuf::from(data)),
             Err(err) => Err(err.to_string()),
         };
 
+        span.end(result.is_err());
+
         let result_data = bincode::serialize(&result).unwrap();
         let buffer_len = result_data.len();
 
@@ -136,6 +196,19 @@ fn http_get(
     })
 }
 
+/// Starts a span for an outgoing call of the guest and adds its context to the request headers
+fn start_call_span(
+    caller: &Caller<'_, BindingsData>,
+    name: String,
+    headers: &mut HeaderMap,
+) -> Span {
+    let parent = caller.data().request.get_request().trace;
+    let span = Span::start(name, SpanKind::Client, parent.as_ref());
+
+    headers.insert(TRACEPARENT_HEADER, span.context().to_header_value());
+    span
+}
+
 pub fn get_imports(linker: &mut Linker<BindingsData>) {
     linker
         .func_wrap5_async("ol_ipc", "function_call", function_call)
@@ -2,28 +2,53 @@ use wasmtime::{Caller, Linker};
 
 use super::{get_str, BindingsData};
 
+use crate::logs::{LogLevel, LogLine};
+
+/// Writes a message of the guest to the worker's log and captures it for the caller
+fn record(caller: &Caller<'_, BindingsData>, level: LogLevel, message: &str) {
+    let request = caller.data().request.get_request();
+    let function = &request.function;
+    let invocation_id = &request.invocation_id;
+
+    match level {
+        LogLevel::Debug => log::debug!("[{function} {invocation_id}] {message}"),
+        LogLevel::Info => log::info!("[{function} {invocation_id}] {message}"),
+        LogLevel::Error => log::error!("[{function} {invocation_id}] Error: {message}"),
+        LogLevel::Fatal => log::error!("[{function} {invocation_id}] Fatal error: {message}"),
+    }
+
+    let line = LogLine::new(level, function, invocation_id, message);
+    caller
+        .data()
+        .args
+        .get_result_handle()
+        .lock()
+        .logs
+        .push(line);
+}
+
 fn log_info(mut caller: Caller<'_, BindingsData>, ptr: i32, len: u32) {
     let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
     let log_msg = get_str(&caller, &memory, ptr, len);
-    log::info!("Program: {log_msg}");
+    record(&caller, LogLevel::Info, log_msg);
 }
 
 fn log_debug(mut caller: Caller<'_, BindingsData>, ptr: i32, len: u32) {
     let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
     let log_msg = get_str(&caller, &memory, ptr, len);
-    log::debug!("Program: {log_msg}");
+    record(&caller, LogLevel::Debug, log_msg);
 }
 
 fn log_error(mut caller: Caller<'_, BindingsData>, ptr: i32, len: u32) {
     let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
     let log_msg = get_str(&caller, &memory, ptr, len);
-    log::error!("Program has error: {log_msg}");
+    record(&caller, LogLevel::Error, log_msg);
 }
 
 fn log_fatal(mut caller: Caller<'_, BindingsData>, ptr: i32, len: u32) {
     let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
     let log_msg = get_str(&caller, &memory, ptr, len);
-    log::error!("Program has fatal error: {log_msg}");
+    record(&caller, LogLevel::Fatal, log_msg);
 }
 
 pub fn get_imports(linker: &mut Linker<BindingsData>) {
@@ -1,33 +1,47 @@
 use wasmtime::{Caller, Memory, Val};
 
 use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::time::Instant;
 
 pub mod args;
 pub mod config;
 pub mod ipc;
 pub mod log;
+pub mod request;
 
 use args::ResultHandle;
+use request::RequestInfo;
+
+use crate::address::WorkerAddr;
+use crate::limits::InstanceLimits;
 
 /// All bindings data for a specific instance
 pub struct BindingsData {
     pub args: args::ArgsData,
     pub ipc: ipc::IpcData,
     pub config: config::ConfigData,
+    pub request: request::RequestData,
+    pub limits: InstanceLimits,
+    /// When the current call has to be interrupted (if it has a timeout)
+    pub deadline: Option<Instant>,
 }
 
 impl BindingsData {
     pub fn new(
-        addr: SocketAddr,
+        addr: WorkerAddr,
         config_values: HashMap<String, String>,
         args: Vec<u8>,
+        request: RequestInfo,
         result: ResultHandle,
+        limits: InstanceLimits,
     ) -> Self {
         Self {
             ipc: ipc::IpcData::new(addr),
             args: args::ArgsData::new(args, result),
             config: config::ConfigData::new(config_values),
+            request: request::RequestData::new(request),
+            limits,
+            deadline: None,
         }
     }
 }
@@ -0,0 +1,169 @@
+use std::future::Future;
+
+use std::sync::Arc;
+
+use hyper::header::HeaderMap;
+
+use super::{call_allocate, fill_slice, set_u64, BindingsData};
+
+use wasmtime::{Caller, Linker};
+
+use crate::auth::Identity;
+use crate::trace::TraceContext;
+
+/// Metadata of the HTTP request that triggered a call
+#[derive(Clone, Debug, Default)]
+pub struct RequestInfo {
+    /// The function as requested by the client (e.g., `hashing@3`)
+    pub function: String,
+    /// Tags the log lines of this call
+    pub invocation_id: String,
+    pub method: String,
+    /// The part of the path after the function name (e.g., `/users/1` for `/run/f/users/1`)
+    pub path: String,
+    /// The raw query string (without the leading `?`)
+    pub query: String,
+    pub headers: HeaderMap,
+    /// The verified client (if authentication is enabled)
+    pub identity: Option<Arc<Identity>>,
+    /// The span of this invocation, which calls made by the guest are part of
+    pub trace: Option<TraceContext>,
+}
+
+impl RequestInfo {
+    /// Header names and values as sent to the guest
+    ///
+    /// Values that are not valid UTF-8 are converted lossily.
+    fn get_header_list(&self) -> Vec<(String, String)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+pub struct RequestData {
+    request: RequestInfo,
+}
+
+impl RequestData {
+    pub fn new(request: RequestInfo) -> Self {
+        Self { request }
+    }
+
+    pub fn set_request(&mut self, request: RequestInfo) {
+        self.request = request;
+    }
+
+    pub fn get_request(&self) -> &RequestInfo {
+        &self.request
+    }
+}
+
+/// Copies data into a newly allocated guest buffer
+///
+/// Returns 0 if there is no data, so that the guest does not have to free anything.
+async fn return_buffer(caller: &mut Caller<'_, BindingsData>, data: &[u8], len_out: i32) -> i64 {
+    let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+
+    if data.is_empty() {
+        set_u64(caller, &memory, len_out, 0);
+        return 0;
+    }
+
+    let offset = call_allocate(caller, data.len() as u32).await;
+
+    fill_slice(caller, &memory, offset, data);
+    set_u64(caller, &memory, len_out, data.len() as u64);
+
+    offset as i64
+}
+
+fn get_method(
+    mut caller: Caller<'_, BindingsData>,
+    len_out: i32,
+) -> Box<dyn Future<Output = i64> + Send + '_> {
+    Box::new(async move {
+        log::trace!("Got \"get_method\" call");
+
+        let method = caller.data().request.request.method.clone();
+        return_buffer(&mut caller, method.as_bytes(), len_out).await
+    })
+}
+
+fn get_path(
+    mut caller: Caller<'_, BindingsData>,
+    len_out: i32,
+) -> Box<dyn Future<Output = i64> + Send + '_> {
+    Box::new(async move {
+        log::trace!("Got \"get_path\" call");
+
+        let path = caller.data().request.request.path.clone();
+        return_buffer(&mut caller, path.as_bytes(), len_out).await
+    })
+}
+
+fn get_query(
+    mut caller: Caller<'_, BindingsData>,
+    len_out: i32,
+) -> Box<dyn Future<Output = i64> + Send + '_> {
+    Box::new(async move {
+        log::trace!("Got \"get_query\" call");
+
+        let query = caller.data().request.request.query.clone();
+        return_buffer(&mut caller, query.as_bytes(), len_out).await
+    })
+}
+
+fn get_headers(
+    mut caller: Caller<'_, BindingsData>,
+    len_out: i32,
+) -> Box<dyn Future<Output = i64> + Send + '_> {
+    Box::new(async move {
+        log::trace!("Got \"get_headers\" call");
+
+        let headers = caller.data().request.request.get_header_list();
+        let data = bincode::serialize(&headers).unwrap();
+        return_buffer(&mut caller, &data, len_out).await
+    })
+}
+
+fn get_identity(
+    mut caller: Caller<'_, BindingsData>,
+    len_out: i32,
+) -> Box<dyn Future<Output = i64> + Send + '_> {
+    Box::new(async move {
+        log::trace!("Got \"get_identity\" call");
+
+        let data = match &caller.data().request.request.identity {
+            Some(identity) => serde_json::to_vec(identity.as_ref()).unwrap(),
+            None => vec![],
+        };
+        return_buffer(&mut caller, &data, len_out).await
+    })
+}
+
+pub fn get_imports(linker: &mut Linker<BindingsData>) {
+    let module = "ol_request";
+
+    linker
+        .func_wrap1_async(module, "get_method", get_method)
+        .unwrap();
+    linker
+        .func_wrap1_async(module, "get_path", get_path)
+        .unwrap();
+    linker
+        .func_wrap1_async(module, "get_query", get_query)
+        .unwrap();
+    linker
+        .func_wrap1_async(module, "get_headers", get_headers)
+        .unwrap();
+    linker
+        .func_wrap1_async(module, "get_identity", get_identity)
+        .unwrap();
+}
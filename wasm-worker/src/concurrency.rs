@@ -0,0 +1,88 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// How many calls may wait for a slot if no queue length is configured
+const DEFAULT_MAX_QUEUE_LENGTH: usize = 100;
+
+/// Why a call could not get an execution slot
+#[derive(Debug)]
+pub enum QueueError {
+    /// Too many calls are already waiting
+    Full,
+    /// The call waited longer than the queue timeout
+    Timeout,
+}
+
+/// Limits how many calls of a function can run at the same time
+///
+/// Calls beyond the limit wait in a bounded FIFO queue.
+pub struct ConcurrencyLimit {
+    semaphore: Semaphore,
+    num_queued: AtomicUsize,
+    max_queue_length: usize,
+    queue_timeout: Option<Duration>,
+}
+
+/// Keeps track of the number of waiting calls, even if a call is cancelled
+struct QueueGuard<'a> {
+    num_queued: &'a AtomicUsize,
+}
+
+impl Drop for QueueGuard<'_> {
+    fn drop(&mut self) {
+        self.num_queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl ConcurrencyLimit {
+    pub fn new(
+        max_concurrency: usize,
+        max_queue_length: Option<usize>,
+        queue_timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            semaphore: Semaphore::new(max_concurrency),
+            num_queued: AtomicUsize::new(0),
+            max_queue_length: max_queue_length.unwrap_or(DEFAULT_MAX_QUEUE_LENGTH),
+            queue_timeout,
+        }
+    }
+
+    pub fn num_queued(&self) -> usize {
+        self.num_queued.load(Ordering::SeqCst)
+    }
+
+    /// Waits until another call is allowed to run
+    ///
+    /// The slot is released once the returned permit is dropped.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, QueueError> {
+        // Succeeds only if nobody else is waiting, so this does not skip the queue
+        if let Ok(permit) = self.semaphore.try_acquire() {
+            return Ok(permit);
+        }
+
+        if self.num_queued.fetch_add(1, Ordering::SeqCst) >= self.max_queue_length {
+            self.num_queued.fetch_sub(1, Ordering::SeqCst);
+            return Err(QueueError::Full);
+        }
+
+        let _guard = QueueGuard {
+            num_queued: &self.num_queued,
+        };
+
+        let acquire = self.semaphore.acquire();
+
+        let result = if let Some(timeout) = self.queue_timeout {
+            match tokio::time::timeout(timeout, acquire).await {
+                Ok(result) => result,
+                Err(_) => return Err(QueueError::Timeout),
+            }
+        } else {
+            acquire.await
+        };
+
+        Ok(result.expect("Semaphore was closed"))
+    }
+}
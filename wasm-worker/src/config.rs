@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use serde::Deserialize;
+
+use wasmtime::{InstanceAllocationStrategy, PoolingAllocationConfig};
+
+use crate::auth::AuthConfig;
+use crate::invocations::InvocationConfig;
+use crate::logs::LogConfig;
+use crate::ratelimit::RateLimitConfig;
+use crate::scheduler::{self, ScheduleConfig};
+use crate::server::HttpProtocol;
+use crate::settings::SettingsTable;
+use crate::trace::TracingConfig;
+use crate::triggers::{self, DirectoryTriggerConfig};
+
+/// wasmtime's default for the number of instances in the pool
+const DEFAULT_TOTAL_CORE_INSTANCES: u32 = 1000;
+
+/// The configuration of the worker, as read from a TOML or JSON file
+///
+/// Every field is optional; command line arguments take precedence over the file.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorkerConfig {
+    pub listen_address: String,
+    pub registry_path: String,
+    pub http_protocol: HttpProtocol,
+    pub tls: Option<TlsConfig>,
+    /// API keys and JWT verification for invoking functions
+    pub auth: AuthConfig,
+    /// Limits for individual clients (function limits are per-function settings)
+    pub rate_limits: RateLimitConfig,
+    /// Where to write the spans of invocations and calls they make
+    pub tracing: TracingConfig,
+    /// How many log lines of guests are kept per function
+    pub logs: LogConfig,
+    pub shutdown_grace_period_ms: u64,
+    pub engine: EngineConfig,
+    pub pooling: PoolingConfig,
+    /// Limits for asynchronous invocations
+    pub invocations: InvocationConfig,
+    /// Functions to invoke periodically
+    pub schedules: Vec<ScheduleConfig>,
+    /// Directories in which every new file invokes a function
+    pub directory_triggers: Vec<DirectoryTriggerConfig>,
+    /// Per-function settings, keyed by function name (or '*' for defaults)
+    pub functions: HashMap<String, HashMap<String, SettingValue>>,
+    /// Values that guests can query using `get_config_value`
+    pub config_values: HashMap<String, String>,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: "localhost:5000".to_string(),
+            registry_path: "./test-registry.wasm".to_string(),
+            http_protocol: Default::default(),
+            tls: None,
+            auth: Default::default(),
+            rate_limits: Default::default(),
+            tracing: Default::default(),
+            logs: Default::default(),
+            shutdown_grace_period_ms: 30000,
+            engine: Default::default(),
+            pooling: Default::default(),
+            invocations: Default::default(),
+            schedules: Default::default(),
+            directory_triggers: Default::default(),
+            functions: Default::default(),
+            config_values: Default::default(),
+        }
+    }
+}
+
+/// Certificates used to terminate TLS
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file containing the certificate chain of the worker
+    pub cert_path: PathBuf,
+    /// PEM file containing the private key of the worker
+    pub key_path: PathBuf,
+    /// PEM file containing the CAs for client certificates
+    ///
+    /// If set, clients must present a valid certificate (mTLS).
+    pub client_ca_path: Option<PathBuf>,
+    /// How long clients may take to complete the handshake
+    #[serde(default = "default_handshake_timeout_ms")]
+    pub handshake_timeout_ms: u64,
+}
+
+pub fn default_handshake_timeout_ms() -> u64 {
+    10_000
+}
+
+/// Options for the wasmtime engine
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    pub opt_level: OptLevel,
+    pub parallel_compilation: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            opt_level: OptLevel::Speed,
+            parallel_compilation: true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OptLevel {
+    None,
+    Speed,
+    SpeedAndSize,
+}
+
+/// Limits of the pooling instance allocator
+///
+/// Unset limits keep wasmtime's defaults.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolingConfig {
+    /// Use the pooling allocator instead of allocating instances on demand
+    pub enabled: bool,
+    pub total_core_instances: Option<u32>,
+    pub total_memories: Option<u32>,
+    pub total_tables: Option<u32>,
+    pub total_stacks: Option<u32>,
+    pub max_memory_pages: Option<u64>,
+    pub max_table_elements: Option<u32>,
+    pub max_unused_warm_slots: Option<u32>,
+}
+
+impl Default for PoolingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            total_core_instances: None,
+            total_memories: None,
+            total_tables: None,
+            total_stacks: None,
+            max_memory_pages: None,
+            max_table_elements: None,
+            max_unused_warm_slots: None,
+        }
+    }
+}
+
+/// The value of a per-function setting
+///
+/// Settings are always parsed from strings, so that the config file and
+/// the command line go through the same validation.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Integer(u64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for SettingValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer(value) => write!(f, "{value}"),
+            Self::Float(value) => write!(f, "{value}"),
+            Self::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// Reads the configuration from a file
+    ///
+    /// The format is determined by the file extension (`.toml` or `.json`).
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file at {path:?}"))?;
+
+        let config: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .with_context(|| format!("Failed to parse config file at {path:?}"))?,
+            Some("json") => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse config file at {path:?}"))?,
+            _ => anyhow::bail!("Unsupported config file {path:?}; expected a .toml or .json file"),
+        };
+
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file at {path:?}"))?;
+
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.listen_address.is_empty() {
+            anyhow::bail!("\"listen_address\" must not be empty");
+        }
+
+        if self.registry_path.is_empty() {
+            anyhow::bail!("\"registry_path\" must not be empty");
+        }
+
+        self.pooling.validate()?;
+        self.rate_limits.validate()?;
+        scheduler::validate(&self.schedules)?;
+        triggers::validate(&self.directory_triggers)?;
+
+        if self.invocations.max_invocations == 0 {
+            anyhow::bail!("\"invocations.max_invocations\" must be greater than zero");
+        }
+
+        self.function_settings()?;
+
+        Ok(())
+    }
+
+    /// Builds the settings of all functions that are set in the file
+    pub fn function_settings(&self) -> anyhow::Result<SettingsTable> {
+        let mut table = SettingsTable::default();
+
+        for (name, settings) in self.functions.iter() {
+            for (key, value) in settings.iter() {
+                table
+                    .get_mut(name)
+                    .set(key, &value.to_string())
+                    .with_context(|| format!("Invalid settings for function \"{name}\""))?;
+            }
+        }
+
+        Ok(table)
+    }
+}
+
+impl EngineConfig {
+    pub fn apply(&self, config: &mut wasmtime::Config) {
+        let opt_level = match self.opt_level {
+            OptLevel::None => wasmtime::OptLevel::None,
+            OptLevel::Speed => wasmtime::OptLevel::Speed,
+            OptLevel::SpeedAndSize => wasmtime::OptLevel::SpeedAndSize,
+        };
+
+        config.cranelift_opt_level(opt_level);
+        config.parallel_compilation(self.parallel_compilation);
+    }
+}
+
+impl PoolingConfig {
+    fn validate(&self) -> anyhow::Result<()> {
+        let counts = [
+            ("total_core_instances", self.total_core_instances),
+            ("total_memories", self.total_memories),
+            ("total_tables", self.total_tables),
+            ("total_stacks", self.total_stacks),
+        ];
+
+        for (key, value) in counts {
+            if value == Some(0) {
+                anyhow::bail!("\"pooling.{key}\" must be greater than zero");
+            }
+        }
+
+        if !self.enabled && self.has_limits() {
+            anyhow::bail!("Pooling limits are set, but the pooling allocator is disabled");
+        }
+
+        Ok(())
+    }
+
+    fn has_limits(&self) -> bool {
+        self.total_core_instances.is_some()
+            || self.total_memories.is_some()
+            || self.total_tables.is_some()
+            || self.total_stacks.is_some()
+            || self.max_memory_pages.is_some()
+            || self.max_table_elements.is_some()
+            || self.max_unused_warm_slots.is_some()
+    }
+
+    /// How many instances can exist at once, or `None` if pooling is disabled
+    pub fn capacity(&self) -> Option<u32> {
+        self.enabled.then(|| {
+            self.total_core_instances
+                .unwrap_or(DEFAULT_TOTAL_CORE_INSTANCES)
+        })
+    }
+
+    pub fn get_allocation_strategy(&self) -> InstanceAllocationStrategy {
+        if !self.enabled {
+            return InstanceAllocationStrategy::OnDemand;
+        }
+
+        let mut pooling = PoolingAllocationConfig::default();
+
+        if let Some(count) = self.total_core_instances {
+            pooling.total_core_instances(count);
+        }
+        if let Some(count) = self.total_memories {
+            pooling.total_memories(count);
+        }
+        if let Some(count) = self.total_tables {
+            pooling.total_tables(count);
+        }
+        if let Some(count) = self.total_stacks {
+            pooling.total_stacks(count);
+        }
+        if let Some(pages) = self.max_memory_pages {
+            pooling.memory_pages(pages);
+        }
+        if let Some(elements) = self.max_table_elements {
+            pooling.table_elements(elements);
+        }
+        if let Some(count) = self.max_unused_warm_slots {
+            pooling.max_unused_warm_slots(count);
+        }
+
+        InstanceAllocationStrategy::Pooling(pooling)
+    }
+}
+
+/// Parses config values of the form `<key>=<value>`
+///
+/// Only the first `=` separates key and value, so values may contain `=` themselves.
+pub fn parse_config_values(entries: &[String]) -> anyhow::Result<HashMap<String, String>> {
+    let mut values = HashMap::default();
+
+    for entry in entries {
+        let Some((key, value)) = entry.split_once('=') else {
+            anyhow::bail!("Invalid config value \"{entry}\"; expected <key>=<value>");
+        };
+
+        if key.is_empty() {
+            anyhow::bail!("Invalid config value \"{entry}\"; key must not be empty");
+        }
+
+        values.insert(key.to_string(), value.to_string());
+    }
+
+    Ok(values)
+}
@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+/// Keeps track of function calls that are currently executing
+///
+/// Used on shutdown to wait until all of them have finished.
+#[derive(Default)]
+pub struct InFlightCalls {
+    count: AtomicUsize,
+    draining: AtomicBool,
+    notify: Notify,
+}
+
+/// Marks a call as in-flight until dropped
+pub struct InFlightGuard {
+    calls: Arc<InFlightCalls>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.calls.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.calls.notify.notify_waiters();
+        }
+    }
+}
+
+impl InFlightCalls {
+    /// Registers a new call
+    ///
+    /// Returns `None` if the worker is shutting down and no new calls should be started.
+    pub fn start(self: &Arc<Self>) -> Option<InFlightGuard> {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let guard = InFlightGuard {
+            calls: self.clone(),
+        };
+
+        if self.draining.load(Ordering::SeqCst) {
+            None
+        } else {
+            Some(guard)
+        }
+    }
+
+    pub fn num_running(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Rejects all new calls and waits for the current ones to finish
+    ///
+    /// Returns the number of calls that were still running after `grace_period`.
+    pub async fn drain(&self, grace_period: Duration) -> usize {
+        self.draining.store(true, Ordering::SeqCst);
+
+        let wait_for_calls = async {
+            loop {
+                let notified = self.notify.notified();
+                if self.num_running() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        };
+
+        let _ = tokio::time::timeout(grace_period, wait_for_calls).await;
+        self.num_running()
+    }
+}
@@ -0,0 +1,102 @@
+use http_body_util::Full;
+
+use hyper::body::Bytes;
+use hyper::{header, http, Response, StatusCode};
+
+use serde::Serialize;
+
+use wasmtime::{Trap, WasmBacktrace};
+
+use crate::bindings::args::ResultError;
+use crate::limits::LimitError;
+
+/// At which point in handling the request something went wrong
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorStage {
+    /// The request could not be mapped to an endpoint or function
+    Routing,
+    /// The client could not be authenticated or may not invoke the function
+    Auth,
+    /// The call exceeded the rate limit of the function or the client
+    RateLimit,
+    /// The call could not get an execution slot
+    Queue,
+    /// A function could not be deployed or (re-)configured
+    Deploy,
+    /// Creating or setting up the WebAssembly instance failed
+    Instantiate,
+    /// The guest trapped while executing
+    Trap,
+    /// The guest did not finish before its timeout expired
+    Timeout,
+    /// The guest exceeded one of its resource limits
+    Limit,
+    /// The guest finished, but its result could not be processed
+    Result,
+}
+
+/// The JSON body of all error responses generated by the worker
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub stage: ErrorStage,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trap: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub backtrace: Vec<String>,
+}
+
+impl ErrorResponse {
+    pub fn new<S: ToString>(stage: ErrorStage, message: S) -> Self {
+        Self {
+            stage,
+            message: message.to_string(),
+            trap: None,
+            backtrace: vec![],
+        }
+    }
+
+    /// Generates an error from a failed call into the guest
+    pub fn from_call_error(error: &anyhow::Error) -> Self {
+        let stage = if error.downcast_ref::<ResultError>().is_some() {
+            ErrorStage::Result
+        } else if error.downcast_ref::<LimitError>().is_some() {
+            ErrorStage::Limit
+        } else {
+            ErrorStage::Trap
+        };
+
+        let trap = error.downcast_ref::<Trap>().map(|trap| trap.to_string());
+
+        let backtrace = match error.downcast_ref::<WasmBacktrace>() {
+            Some(backtrace) => backtrace
+                .frames()
+                .iter()
+                .map(|frame| match frame.func_name() {
+                    Some(name) => name.to_string(),
+                    None => format!("<wasm function {}>", frame.func_index()),
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Self {
+            stage,
+            message: error.root_cause().to_string(),
+            trap,
+            backtrace,
+        }
+    }
+
+    pub fn into_response(self, status: StatusCode) -> http::Result<Response<Full<Bytes>>> {
+        let body = serde_json::to_vec(&self).expect("Failed to serialize error");
+
+        Response::builder()
+            .status(status)
+            .header(header::CONTENT_TYPE, "application/json")
+            // Lets internal callers see why a call failed without parsing the body
+            .extension(self.stage)
+            .body(body.into())
+    }
+}
@@ -1,21 +1,40 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::io::{Read, Write};
-use std::net::SocketAddr;
-use std::path::PathBuf;
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use anyhow::Context;
 
 use dashmap::DashMap;
 
-use wasmtime::{AsContextMut, Engine, Instance, Linker, Module, Store};
+use tokio::sync::SemaphorePermit;
 
-use crate::bindings::{self, args::ResultHandle, BindingsData};
+use serde::Serialize;
+
+use sha2::{Digest, Sha256};
+
+use wasmtime::{AsContextMut, Engine, Instance, Linker, Module, Store, Trap, UpdateDeadline};
+
+use crate::address::WorkerAddr;
+use crate::bindings::{self, args::ResultHandle, request::RequestInfo, BindingsData};
+use crate::concurrency::{ConcurrencyLimit, QueueError};
+use crate::config::{EngineConfig, PoolingConfig};
+use crate::limits::InstanceLimits;
+use crate::metrics;
+use crate::settings::{FunctionSettings, SettingsTable};
+use crate::versions::{AliasTable, FunctionId, VersionWeights, DEFAULT_ALIAS};
 
 const MAX_IDLE_INSTANCES: usize = 100;
 
+/// How often the engine's epoch is incremented; this is the granularity of timeouts
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// Fuel for instances without a fuel budget (effectively infinite)
+const NO_FUEL_LIMIT: u64 = u64::MAX;
+
 pub type InstanceId = u64;
 
 type IdleInstancesList = crossbeam::queue::SegQueue<InstanceData>;
@@ -23,8 +42,12 @@ type IdleInstancesList = crossbeam::queue::SegQueue<InstanceData>;
 pub struct Function {
     next_instance_id: Arc<AtomicU64>,
     idle_list: Arc<IdleInstancesList>,
+    num_busy: Arc<AtomicUsize>,
     engine: Arc<Engine>,
     module: Arc<Module>,
+    info: FunctionInfo,
+    settings: FunctionSettings,
+    concurrency: Option<ConcurrencyLimit>,
 }
 
 struct InstanceData {
@@ -36,45 +59,147 @@ struct InstanceData {
 pub struct InstanceHandle {
     idle_list: Arc<IdleInstancesList>,
     data: InstanceData,
+    cold_start: bool,
+    fuel_budget: u64,
+    _busy: BusyGuard,
+}
+
+/// Tracks the number of instances that are currently in use
+struct BusyGuard {
+    num_busy: Arc<AtomicUsize>,
+}
+
+impl BusyGuard {
+    fn new(num_busy: Arc<AtomicUsize>) -> Self {
+        num_busy.fetch_add(1, Ordering::Relaxed);
+        Self { num_busy }
+    }
+}
+
+impl Drop for BusyGuard {
+    fn drop(&mut self) {
+        self.num_busy.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 impl Function {
+    pub fn info(&self) -> &FunctionInfo {
+        &self.info
+    }
+
+    pub fn settings(&self) -> &FunctionSettings {
+        &self.settings
+    }
+
+    /// Waits until the function is allowed to run another call
+    ///
+    /// Returns `None` if the function has no concurrency limit.
+    pub async fn acquire_slot(&self) -> Result<Option<SemaphorePermit<'_>>, QueueError> {
+        match &self.concurrency {
+            Some(limit) => limit.acquire().await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn num_queued_calls(&self) -> usize {
+        self.concurrency
+            .as_ref()
+            .map(|limit| limit.num_queued())
+            .unwrap_or(0)
+    }
+
+    pub fn num_idle_instances(&self) -> usize {
+        self.idle_list.len()
+    }
+
+    pub fn num_busy_instances(&self) -> usize {
+        self.num_busy.load(Ordering::Relaxed)
+    }
+
     pub async fn get_idle_instance(
         &self,
         args: Vec<u8>,
+        request: RequestInfo,
         config_values: &HashMap<String, String>,
-        addr: SocketAddr,
+        addr: &WorkerAddr,
         result_hdl: ResultHandle,
-    ) -> InstanceHandle {
+    ) -> anyhow::Result<InstanceHandle> {
         if let Some(mut data) = self.idle_list.pop() {
             log::trace!("Reusing WASM instance with id={}", data.get_identifier());
-            data.refresh(config_values, addr, args, result_hdl);
+            data.refresh(config_values, addr, args, request, result_hdl);
 
-            InstanceHandle::new(self.idle_list.clone(), data)
+            Ok(InstanceHandle::new(self, data, false))
         } else {
             let identifier = self.next_instance_id.fetch_add(1, Ordering::SeqCst);
 
             log::trace!("Creating new WASM instance with id={identifier}");
 
+            let _timer = metrics::INSTANTIATE_TIME
+                .with_label_values(&[&self.info.id().to_string()])
+                .start_timer();
+
             let data = InstanceData::new(
                 &self.engine,
                 &self.module,
                 identifier,
                 config_values.clone(),
-                addr,
+                addr.clone(),
                 args,
+                request,
                 result_hdl,
+                self.settings.get_instance_limits(),
             )
-            .await;
+            .await?;
 
-            InstanceHandle::new(self.idle_list.clone(), data)
+            Ok(InstanceHandle::new(self, data, true))
         }
     }
 }
 
 impl InstanceHandle {
-    fn new(idle_list: Arc<IdleInstancesList>, data: InstanceData) -> Self {
-        Self { idle_list, data }
+    fn new(function: &Function, data: InstanceData, cold_start: bool) -> Self {
+        Self {
+            idle_list: function.idle_list.clone(),
+            data,
+            cold_start,
+            fuel_budget: NO_FUEL_LIMIT,
+            _busy: BusyGuard::new(function.num_busy.clone()),
+        }
+    }
+
+    /// Sets the maximum time the next call into this instance may take
+    ///
+    /// Once it expires, the guest traps with `Trap::Interrupt` at its next epoch tick.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.data.store.data_mut().deadline = timeout.map(|timeout| Instant::now() + timeout);
+        self.data.store.set_epoch_deadline(1);
+    }
+
+    /// Sets how much fuel the next call may consume
+    ///
+    /// Once the fuel runs out, the guest traps with `Trap::OutOfFuel`.
+    pub fn set_fuel(
+        &mut self,
+        budget: Option<u64>,
+        yield_interval: Option<u64>,
+    ) -> anyhow::Result<()> {
+        self.fuel_budget = budget.unwrap_or(NO_FUEL_LIMIT);
+
+        self.data.store.set_fuel(self.fuel_budget)?;
+        self.data.store.fuel_async_yield_interval(yield_interval)?;
+
+        Ok(())
+    }
+
+    /// How much fuel has been consumed since the last call to `set_fuel`
+    pub fn get_fuel_consumed(&self) -> u64 {
+        let remaining = self.data.store.get_fuel().unwrap_or(0);
+        self.fuel_budget.saturating_sub(remaining)
+    }
+
+    /// Was this instance newly created for this call?
+    pub fn is_cold_start(&self) -> bool {
+        self.cold_start
     }
 
     pub fn get(&mut self) -> (&mut Store<BindingsData>, &Instance) {
@@ -96,7 +221,6 @@ impl InstanceHandle {
         }
     }
 
-    #[allow(dead_code)]
     pub fn discard(self) {
         log::trace!(
             "Discarding instance with id={} as requested",
@@ -105,123 +229,476 @@ impl InstanceHandle {
     }
 }
 
+/// Metadata about a loaded function
+#[derive(Clone, Debug, Serialize)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub version: Option<u64>,
+    /// Size of the WebAssembly file in bytes
+    pub size: u64,
+    /// When the function was loaded (in seconds since the UNIX epoch)
+    pub loaded_at: u64,
+    /// How long it took to load (and possibly compile) the function
+    pub load_duration_ms: u64,
+    /// Was the compiled module loaded from the cache?
+    pub cache_hit: bool,
+    #[serde(skip)]
+    modified: SystemTime,
+}
+
+impl FunctionInfo {
+    pub fn id(&self) -> FunctionId {
+        FunctionId::new(&self.name, self.version)
+    }
+}
+
+/// State of the compilation cache
+#[derive(Debug, Serialize)]
+pub struct CacheStatus {
+    pub path: PathBuf,
+    /// Number of compiled modules in the cache
+    pub num_entries: usize,
+    pub size_bytes: u64,
+    /// How many of the loaded functions were read from the cache instead of being compiled
+    pub num_loaded_from_cache: usize,
+}
+
+/// Usage of the pooling instance allocator
+#[derive(Debug, Serialize)]
+pub struct PoolingStatus {
+    pub enabled: bool,
+    /// How many instances can exist at once (`None` if pooling is disabled)
+    pub capacity: Option<u32>,
+    /// Instances that are allocated, whether they are running a call or idle
+    pub num_instances: usize,
+    pub num_busy_instances: usize,
+}
+
 pub struct FunctionManager {
     functions: Arc<DashMap<String, Arc<Function>>>,
     next_instance_id: Arc<AtomicU64>,
     engine: Arc<wasmtime::Engine>,
+    pool_capacity: Option<u32>,
+    registry_path: PathBuf,
+    cache_path: PathBuf,
+    aliases: AliasTable,
+    settings: SettingsTable,
 }
 
 impl FunctionManager {
-    pub async fn new() -> anyhow::Result<Self> {
+    pub async fn new(
+        registry_path: &str,
+        engine_config: &EngineConfig,
+        pooling_config: &PoolingConfig,
+        settings: SettingsTable,
+    ) -> anyhow::Result<Self> {
         let next_instance_id = Arc::new(AtomicU64::new(1));
         let mut config = wasmtime::Config::new();
         config.async_support(true);
 
-        // Optimize for performance
-        config.cranelift_opt_level(wasmtime::OptLevel::Speed);
-        config.allocation_strategy(wasmtime::InstanceAllocationStrategy::pooling());
+        // Needed to interrupt guests that exceed their timeout
+        config.epoch_interruption(true);
+
+        // Needed to measure (and limit) the compute used by guests
+        config.consume_fuel(true);
+
+        engine_config.apply(&mut config);
+        config.allocation_strategy(pooling_config.get_allocation_strategy());
 
         let engine =
             wasmtime::Engine::new(&config).with_context(|| "Failed to create wasmtime engine")?;
+        let engine = Arc::new(engine);
+
+        // Use a dedicated thread, so that the epoch advances even if guests block all runtime threads
+        {
+            let engine = engine.clone();
+            std::thread::Builder::new()
+                .name("epoch-ticker".to_string())
+                .spawn(move || loop {
+                    std::thread::sleep(EPOCH_TICK);
+                    engine.increment_epoch();
+                })
+                .with_context(|| "Failed to spawn epoch thread")?;
+        }
 
         Ok(Self {
             functions: Default::default(),
-            engine: Arc::new(engine),
+            engine,
             next_instance_id,
+            pool_capacity: pooling_config.capacity(),
+            registry_path: registry_path.into(),
+            cache_path: format!("{registry_path}.cache").into(),
+            aliases: AliasTable::load(format!("{registry_path}.aliases.json").into())?,
+            settings,
         })
     }
 
+    /// Looks up the function for a call
+    ///
+    /// The name can either be plain (`name`), refer to a specific version (`name@3`),
+    /// or refer to an alias (`name@prod`). Plain names resolve to the default alias if
+    /// there is one, then to the unversioned module, and finally to the latest version.
     pub async fn get_function(&self, name: &str) -> Option<Arc<Function>> {
-        self.functions.get(name).map(|entry| entry.value().clone())
+        let (name, selector) = match name.split_once('@') {
+            Some((name, selector)) => (name, Some(selector)),
+            None => (name, None),
+        };
+
+        let version = match selector {
+            Some(selector) => match selector.parse::<u64>() {
+                Ok(version) => Some(version),
+                Err(_) => Some(self.aliases.pick(name, selector)?),
+            },
+            None => self.aliases.pick(name, DEFAULT_ALIAS),
+        };
+
+        if version.is_some() {
+            let id = FunctionId::new(name, version);
+            return self
+                .functions
+                .get(&id.to_string())
+                .map(|entry| entry.value().clone());
+        }
+
+        if let Some(entry) = self.functions.get(name) {
+            return Some(entry.value().clone());
+        }
+
+        self.functions
+            .iter()
+            .filter(|entry| entry.value().info.name == name)
+            .max_by_key(|entry| entry.value().info.version)
+            .map(|entry| entry.value().clone())
     }
 
-    pub async fn load_function(&self, path: PathBuf, cache_path: PathBuf) {
-        let os_name = path.file_stem().unwrap();
-        let name = String::from(os_name.to_str().unwrap());
+    pub fn get_aliases(&self, name: &str) -> BTreeMap<String, VersionWeights> {
+        self.aliases.get(name)
+    }
 
-        let cpath = cache_path.join(format!("{name}.bin"));
+    /// Points an alias to one or multiple versions of a function
+    pub fn set_alias(
+        &self,
+        name: &str,
+        alias: &str,
+        weights: VersionWeights,
+    ) -> anyhow::Result<()> {
+        for version in weights.keys() {
+            let id = FunctionId::new(name, Some(*version));
+
+            if !self.functions.contains_key(&id.to_string()) {
+                anyhow::bail!("No such function \"{id}\"");
+            }
+        }
 
-        log::debug!("Loading function \"{name}\" with path {path:?}");
-        let mut file = match fs::File::open(&path) {
-            Ok(f) => f,
-            Err(err) => {
-                log::error!("Failed to open function file at \"{path:?}\": {err}");
-                std::process::exit(1);
+        self.aliases.set(name, alias, weights)?;
+        log::info!("Updated alias \"{alias}\" of function \"{name}\"");
+
+        Ok(())
+    }
+
+    pub fn remove_alias(&self, name: &str, alias: &str) -> anyhow::Result<bool> {
+        self.aliases.remove(name, alias)
+    }
+
+    pub fn get_functions(&self) -> Vec<Arc<Function>> {
+        self.functions
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect()
+    }
+
+    pub fn num_functions(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn cache_status(&self) -> CacheStatus {
+        let mut num_entries = 0;
+        let mut size_bytes = 0;
+
+        // The cache directory only exists once something has been compiled
+        if let Ok(directory) = fs::read_dir(&self.cache_path) {
+            for entry in directory.flatten() {
+                if entry.path().extension().is_some_and(|ext| ext == "bin") {
+                    num_entries += 1;
+                    size_bytes += entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+                }
             }
+        }
+
+        let num_loaded_from_cache = self
+            .functions
+            .iter()
+            .filter(|entry| entry.value().info.cache_hit)
+            .count();
+
+        CacheStatus {
+            path: self.cache_path.clone(),
+            num_entries,
+            size_bytes,
+            num_loaded_from_cache,
+        }
+    }
+
+    pub fn pooling_status(&self) -> PoolingStatus {
+        let mut num_instances = 0;
+        let mut num_busy_instances = 0;
+
+        for entry in self.functions.iter() {
+            let function = entry.value();
+            num_busy_instances += function.num_busy_instances();
+            num_instances += function.num_busy_instances() + function.num_idle_instances();
+        }
+
+        PoolingStatus {
+            enabled: self.pool_capacity.is_some(),
+            capacity: self.pool_capacity,
+            num_instances,
+            num_busy_instances,
+        }
+    }
+
+    /// Lists all currently loaded functions, sorted by name
+    pub fn list_functions(&self) -> Vec<FunctionInfo> {
+        let mut result: Vec<_> = self
+            .functions
+            .iter()
+            .map(|entry| entry.value().info.clone())
+            .collect();
+
+        result.sort_by(|a, b| (&a.name, a.version).cmp(&(&b.name, b.version)));
+        result
+    }
+
+    /// Removes a function from the manager
+    ///
+    /// Calls that are already running keep their reference to the old module
+    /// and will finish normally.
+    pub fn unload_function(&self, name: &str) -> bool {
+        if self.functions.remove(name).is_some() {
+            log::info!("Unloaded function \"{name}\"");
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Stores the given WebAssembly code in the registry and loads it
+    ///
+    /// The registry is only modified if the code compiled successfully.
+    pub async fn deploy_function(&self, name: &str, code: &[u8]) -> anyhow::Result<FunctionInfo> {
+        let Some(id) = FunctionId::parse(name) else {
+            anyhow::bail!("Invalid function name \"{name}\"");
         };
+        let name = id.to_string();
 
-        let is_cached = {
-            let file_meta = fs::metadata(&path).expect("Failed to read file metadata");
-
-            match fs::metadata(&cpath) {
-                Ok(cache_meta) => cache_meta.modified().unwrap() > file_meta.modified().unwrap(),
-                Err(err) => {
-                    if err.kind() == std::io::ErrorKind::NotFound {
-                        false
-                    } else {
-                        panic!("Failed to read cachefile metadata: {err}");
-                    }
-                }
+        // Use an extension that the registry watcher ignores, and a unique name so that
+        // concurrent uploads of the same function do not write to the same file
+        let upload_id = uuid::Uuid::new_v4().simple();
+        let tmp_path = self
+            .registry_path
+            .join(format!(".{name}.{upload_id}.upload"));
+        let path = self.registry_path.join(format!("{name}.wasm"));
+
+        fs::write(&tmp_path, code)
+            .with_context(|| format!("Failed to write function file at {tmp_path:?}"))?;
+
+        let function = match self.compile_function(&id, &tmp_path) {
+            Ok(function) => function,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_path);
+                return Err(err);
             }
         };
 
-        let module = if is_cached {
-            // Load cached data
-            let mut file = fs::File::open(cpath).unwrap();
+        // Renaming keeps the modification time, so if the watcher loads the file
+        // after it has been inserted below, it will skip it
+        fs::rename(&tmp_path, &path)
+            .with_context(|| format!("Failed to move function file to {path:?}"))?;
+
+        let info = function.info.clone();
+        self.insert_function(function);
+
+        Ok(info)
+    }
+
+    /// Removes the function from the manager and the registry
+    pub fn delete_function(&self, name: &str) -> anyhow::Result<bool> {
+        let Some(id) = FunctionId::parse(name) else {
+            anyhow::bail!("Invalid function name \"{name}\"");
+        };
+        let name = id.to_string();
+
+        let path = self.registry_path.join(format!("{name}.wasm"));
+
+        let removed = match fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => false,
+            Err(err) => anyhow::bail!("Failed to remove function file at {path:?}: {err}"),
+        };
+
+        let _ = fs::remove_file(self.cache_path.join(format!("{name}.bin")));
+
+        // The registry watcher might have unloaded the function already
+        let unloaded = self.unload_function(&name);
+
+        Ok(removed || unloaded)
+    }
+
+    /// Loads (or reloads) the function stored at the given path
+    ///
+    /// If a function with the same name already exists, it will be replaced atomically.
+    pub async fn load_function(&self, path: PathBuf) -> anyhow::Result<()> {
+        let Some(id) = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(FunctionId::parse)
+        else {
+            anyhow::bail!("Invalid function path {path:?}");
+        };
+        let name = id.to_string();
 
-            let mut binary = Vec::new();
-            file.read_to_end(&mut binary).unwrap();
+        // Skip files that have not changed since they were last loaded
+        if let Some(existing) = self.functions.get(&name) {
+            let file_meta = fs::metadata(&path).with_context(|| "Failed to read file metadata")?;
 
+            if file_meta.modified()? == existing.info.modified
+                && file_meta.len() == existing.info.size
+            {
+                log::trace!("Function \"{name}\" did not change. Skipping...");
+                return Ok(());
+            }
+        }
+
+        let function = self.compile_function(&id, &path)?;
+        self.insert_function(function);
+
+        Ok(())
+    }
+
+    fn insert_function(&self, function: Function) {
+        let name = function.info.id().to_string();
+
+        // In-flight calls hold on to the old function (and its module),
+        // so this simply redirects new calls to the new version.
+        if self
+            .functions
+            .insert(name.clone(), Arc::new(function))
+            .is_some()
+        {
+            log::info!("Replaced function \"{name}\"");
+        }
+    }
+
+    /// Compiles the function at the given path or loads it from the cache
+    fn compile_function(&self, id: &FunctionId, path: &Path) -> anyhow::Result<Function> {
+        let start = Instant::now();
+        let name = id.to_string();
+        let cpath = self.cache_path.join(format!("{name}.bin"));
+
+        log::debug!("Loading function \"{name}\" with path {path:?}");
+        let mut file = fs::File::open(path)
+            .with_context(|| format!("Failed to open function file at {path:?}"))?;
+
+        let file_meta = fs::metadata(path).with_context(|| "Failed to read file metadata")?;
+
+        let mut code = Vec::new();
+        file.read_to_end(&mut code)?;
+
+        // Cache files start with the hash of the code they were compiled from, so that
+        // replaced files are never mistaken for cached ones (regardless of their mtime)
+        let code_hash = Sha256::digest(&code);
+
+        let cached = match fs::read(&cpath) {
+            Ok(mut binary) if binary.starts_with(&code_hash) => {
+                binary.drain(..code_hash.len());
+                Some(binary)
+            }
+            Ok(_) => None,
+            Err(err) => {
+                if err.kind() == std::io::ErrorKind::NotFound {
+                    None
+                } else {
+                    anyhow::bail!("Failed to read cache file: {err}");
+                }
+            }
+        };
+        let is_cached = cached.is_some();
+
+        let module = if let Some(binary) = cached {
             log::info!("Loaded cached version of function \"{name}\"");
 
             unsafe {
-                Module::deserialize(&self.engine, &binary).expect("Failed to deserialize module")
+                Module::deserialize(&self.engine, binary)
+                    .with_context(|| "Failed to deserialize module")?
             }
         } else {
-            let mut code = Vec::new();
-            file.read_to_end(&mut code).unwrap();
-
             // Load and compile
             let module = match Module::new(&self.engine, code) {
                 Ok(module) => {
-                    log::info!("Compiled fucntion \"{name}\"");
+                    log::info!("Compiled function \"{name}\"");
                     module
                 }
-                Err(err) => panic!("Failed to compile wasm file \"{name}\": {err:?}"),
+                Err(err) => anyhow::bail!("Failed to compile wasm file \"{name}\": {err:?}"),
             };
 
-            let binary = module.serialize().unwrap();
+            let binary = module.serialize()?;
 
             // Cache binary
-            if let Err(err) = fs::create_dir(&cache_path) {
+            if let Err(err) = fs::create_dir(&self.cache_path) {
                 if err.kind() != std::io::ErrorKind::AlreadyExists {
-                    panic!(
+                    anyhow::bail!(
                         "Failed to create program cache directory at '{}': {err}",
-                        cache_path.display()
+                        self.cache_path.display()
                     );
                 }
             }
 
             {
-                //FIXME add a header with wasmer/compiler version and checksum
-
-                let mut cache_file = fs::File::create(&cpath).expect("Failed to create cache file");
+                // Wasmtime itself rejects modules compiled by a different version or for another CPU
+                let mut cache_file =
+                    fs::File::create(&cpath).with_context(|| "Failed to create cache file")?;
                 cache_file
-                    .write_all(&binary)
-                    .expect("Failed to write cache file");
+                    .write_all(&code_hash)
+                    .and_then(|()| cache_file.write_all(&binary))
+                    .with_context(|| "Failed to write cache file")?;
                 log::debug!("Stored cached program at \"{}\"", cpath.display());
             }
 
             module
         };
 
-        let function = Arc::new(Function {
+        let info = FunctionInfo {
+            name: id.name.clone(),
+            version: id.version,
+            size: file_meta.len(),
+            loaded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            load_duration_ms: start.elapsed().as_millis() as u64,
+            cache_hit: is_cached,
+            modified: file_meta.modified()?,
+        };
+
+        let settings = self.settings.get(&info.name);
+        let concurrency = settings.max_concurrency.map(|max_concurrency| {
+            ConcurrencyLimit::new(
+                max_concurrency,
+                settings.max_queue_length,
+                settings.queue_timeout,
+            )
+        });
+
+        Ok(Function {
             engine: self.engine.clone(),
             module: Arc::new(module),
             next_instance_id: self.next_instance_id.clone(),
             idle_list: Default::default(),
-        });
-        self.functions.insert(name, function);
+            num_busy: Default::default(),
+            settings,
+            concurrency,
+            info,
+        })
     }
 }
 
@@ -232,50 +709,66 @@ impl InstanceData {
         module: &Module,
         identifier: InstanceId,
         config_values: HashMap<String, String>,
-        addr: SocketAddr,
+        addr: WorkerAddr,
         args: Vec<u8>,
+        request: RequestInfo,
         result_hdl: ResultHandle,
-    ) -> Self {
+        limits: InstanceLimits,
+    ) -> anyhow::Result<Self> {
         let mut linker = Linker::new(engine);
 
-        let data = bindings::BindingsData::new(addr, config_values, args, result_hdl);
+        let data =
+            bindings::BindingsData::new(addr, config_values, args, request, result_hdl, limits);
 
         bindings::args::get_imports(&mut linker);
         bindings::log::get_imports(&mut linker);
         bindings::ipc::get_imports(&mut linker);
         bindings::config::get_imports(&mut linker);
+        bindings::request::get_imports(&mut linker);
 
         let mut store = Store::new(engine, data);
+        store.limiter(|data| &mut data.limits);
+        // Yield on every epoch tick, so that guests that never call into the host
+        // cannot starve the runtime's other tasks (e.g., timers or the accept loop)
+        store.set_epoch_deadline(1);
+        store.epoch_deadline_callback(|ctx| match ctx.data().deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(Trap::Interrupt.into()),
+            _ => Ok(UpdateDeadline::Yield(1)),
+        });
+        store.set_fuel(NO_FUEL_LIMIT)?;
 
         let instance = linker
             .instantiate_async(store.as_context_mut(), module)
             .await
-            .expect("Failed to create instance");
+            .with_context(|| "Failed to create instance")?;
 
-        let _ = instance
-            .get_func(&mut store, "_initialize_instance")
-            .unwrap()
-            .call_async(&mut store, &[], &mut [])
-            .await;
+        if let Some(init_fn) = instance.get_func(&mut store, "_initialize_instance") {
+            init_fn
+                .call_async(&mut store, &[], &mut [])
+                .await
+                .with_context(|| "Failed to initialize instance")?;
+        }
 
-        Self {
+        Ok(Self {
             identifier,
             instance,
             store,
-        }
+        })
     }
 
     /// Make the InstanceData ready to be used for another job
     pub(super) fn refresh(
         &mut self,
         _config_values: &HashMap<String, String>,
-        _addr: SocketAddr,
+        _addr: &WorkerAddr,
         args: Vec<u8>,
+        request: RequestInfo,
         result_hdl: ResultHandle,
     ) {
         let bindings = self.store.data_mut();
 
         bindings.args.set_args(args);
+        bindings.request.set_request(request);
         bindings.args.set_result_handle(result_hdl);
     }
 
@@ -1,9 +1,13 @@
+use anyhow::Context as _;
+
 use tokio::net::ToSocketAddrs;
 
 use hyper::body::Bytes;
 use hyper::client::conn;
-use hyper::Request;
+use hyper::header::HeaderMap;
+use hyper::{Request, StatusCode};
 
+use crate::address::WorkerAddr;
 use crate::support;
 
 use http_body_util::{BodyExt, Full};
@@ -20,11 +24,42 @@ impl HttpClient {
                 panic!("Failed to connect to HTTP server at {server_addr:?}: {err}")
             });
         conn.set_nodelay(true).unwrap();
+
+        Self::handshake(conn).await.expect("HTTP handshake failed")
+    }
+
+    /// Connects to a worker, either over TCP or a UNIX domain socket
+    pub async fn connect(addr: &WorkerAddr) -> anyhow::Result<Self> {
+        match addr {
+            WorkerAddr::Tcp(addr) => {
+                let conn = tokio::net::TcpStream::connect(addr)
+                    .await
+                    .with_context(|| format!("Failed to connect to HTTP server at {addr}"))?;
+                conn.set_nodelay(true)?;
+
+                Ok(Self::handshake(conn)
+                    .await
+                    .with_context(|| "HTTP handshake failed")?)
+            }
+            WorkerAddr::Unix(path) => {
+                let conn = tokio::net::UnixStream::connect(path.as_path())
+                    .await
+                    .with_context(|| format!("Failed to connect to HTTP server at {path:?}"))?;
+
+                Ok(Self::handshake(conn)
+                    .await
+                    .with_context(|| "HTTP handshake failed")?)
+            }
+        }
+    }
+
+    async fn handshake<T>(conn: T) -> Result<Self, hyper::Error>
+    where
+        T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
         let conn = support::TokioIo::new(conn);
 
-        let (request_sender, connection) = conn::http1::handshake(conn)
-            .await
-            .expect("HTTP handshake failed");
+        let (request_sender, connection) = conn::http1::handshake(conn).await?;
 
         tokio::spawn(async move {
             if let Err(err) = connection.await {
@@ -32,29 +67,49 @@ impl HttpClient {
             }
         });
 
-        Self { request_sender }
+        Ok(Self { request_sender })
     }
 
-    pub async fn get(&mut self, path: String) -> Result<Vec<u8>, hyper::Error> {
-        let request = Request::builder()
+    pub async fn get(&mut self, path: String, headers: HeaderMap) -> Result<Vec<u8>, hyper::Error> {
+        let mut request = Request::builder()
             .method("GET")
             .uri(path)
             .body(Full::new(Bytes::from("")))
             .unwrap();
+        request.headers_mut().extend(headers);
 
         let response = self.request_sender.send_request(request).await?;
 
         Ok(response.collect().await?.to_bytes().to_vec())
     }
 
-    pub async fn post(&mut self, path: String, content: Vec<u8>) -> Result<Vec<u8>, hyper::Error> {
-        let request = Request::builder()
+    pub async fn post(
+        &mut self,
+        path: String,
+        headers: HeaderMap,
+        content: Vec<u8>,
+    ) -> Result<Vec<u8>, hyper::Error> {
+        let (_, body) = self.post_with_status(path, headers, content).await?;
+        Ok(body)
+    }
+
+    /// Like `post` but also returns the status code of the response
+    pub async fn post_with_status(
+        &mut self,
+        path: String,
+        headers: HeaderMap,
+        content: Vec<u8>,
+    ) -> Result<(StatusCode, Vec<u8>), hyper::Error> {
+        let mut request = Request::builder()
             .method("POST")
             .uri(path)
             .body(Full::new(Bytes::from(content)))
             .unwrap();
+        request.headers_mut().extend(headers);
 
         let response = self.request_sender.send_request(request).await?;
-        Ok(response.collect().await?.to_bytes().to_vec())
+        let status = response.status();
+
+        Ok((status, response.collect().await?.to_bytes().to_vec()))
     }
 }
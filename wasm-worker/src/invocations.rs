@@ -0,0 +1,338 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+
+use http_body_util::Full;
+
+use hyper::body::Bytes;
+use hyper::header::{self, HeaderMap};
+use hyper::{http, Response, StatusCode};
+
+use parking_lot::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use tokio::task::AbortHandle;
+
+use crate::errors::{ErrorResponse, ErrorStage};
+
+pub type InvocationId = String;
+
+/// Limits for the results of asynchronous invocations
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InvocationConfig {
+    /// How long results are kept after the invocation finished
+    pub result_ttl_secs: u64,
+    /// Maximum number of invocations (running or finished) to keep track of
+    pub max_invocations: usize,
+    /// Maximum combined size of all stored results
+    pub max_result_bytes: usize,
+}
+
+impl Default for InvocationConfig {
+    fn default() -> Self {
+        Self {
+            result_ttl_secs: 300,
+            max_invocations: 10000,
+            max_result_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvocationStatus {
+    Running,
+    Completed,
+    /// The function finished, but its result could not be stored
+    Failed,
+    Cancelled,
+}
+
+/// The response of a finished invocation
+struct StoredResult {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+struct Invocation {
+    function: String,
+    status: InvocationStatus,
+    created_at: SystemTime,
+    finished_at: Option<Instant>,
+    result: Option<StoredResult>,
+    task: Option<AbortHandle>,
+}
+
+/// What clients get to see about an invocation that has no result (yet)
+#[derive(Serialize)]
+pub struct InvocationInfo {
+    pub id: InvocationId,
+    pub function: String,
+    pub status: InvocationStatus,
+    pub created_at: SystemTime,
+}
+
+#[derive(Default)]
+struct InvocationTable {
+    invocations: HashMap<InvocationId, Invocation>,
+    /// Finished invocations, oldest first
+    finished: VecDeque<InvocationId>,
+    result_bytes: usize,
+}
+
+/// Keeps track of asynchronous invocations and their results
+pub struct InvocationStore {
+    config: InvocationConfig,
+    table: Mutex<InvocationTable>,
+}
+
+impl InvocationTable {
+    fn remove(&mut self, id: &str) -> Option<Invocation> {
+        let invocation = self.invocations.remove(id)?;
+
+        if invocation.status != InvocationStatus::Running {
+            self.finished.retain(|other| other != id);
+        }
+        if let Some(result) = &invocation.result {
+            self.result_bytes -= result.body.len();
+        }
+
+        Some(invocation)
+    }
+
+    fn evict_oldest(&mut self) -> bool {
+        match self.finished.pop_front() {
+            Some(id) => {
+                if let Some(result) = self.invocations.remove(&id).and_then(|inv| inv.result) {
+                    self.result_bytes -= result.body.len();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl InvocationStore {
+    pub fn new(config: InvocationConfig) -> Self {
+        Self {
+            config,
+            table: Default::default(),
+        }
+    }
+
+    /// Registers a new invocation
+    ///
+    /// Returns `None` if too many invocations are still running.
+    pub fn create(&self, function: &str) -> Option<InvocationId> {
+        let mut table = self.table.lock();
+        self.remove_expired(&mut table);
+
+        while table.invocations.len() >= self.config.max_invocations {
+            if !table.evict_oldest() {
+                return None;
+            }
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        table.invocations.insert(
+            id.clone(),
+            Invocation {
+                function: function.to_string(),
+                status: InvocationStatus::Running,
+                created_at: SystemTime::now(),
+                finished_at: None,
+                result: None,
+                task: None,
+            },
+        );
+
+        Some(id)
+    }
+
+    /// Sets the task executing an invocation, so that it can be cancelled
+    ///
+    /// Aborts the task if the invocation was cancelled before its task was set.
+    pub fn set_task(&self, id: &str, task: AbortHandle) {
+        match self.table.lock().invocations.get_mut(id) {
+            Some(invocation) if invocation.status == InvocationStatus::Running => {
+                invocation.task = Some(task);
+            }
+            _ => task.abort(),
+        }
+    }
+
+    /// Stores the response of a finished invocation
+    pub fn complete(&self, id: &str, status: StatusCode, headers: HeaderMap, body: Bytes) {
+        let mut table = self.table.lock();
+
+        let Some(invocation) = table.invocations.get_mut(id) else {
+            return;
+        };
+
+        if invocation.status != InvocationStatus::Running {
+            return;
+        }
+
+        // Keep the entry, so that clients learn why there is no result
+        let (invocation_status, status, headers, body) =
+            if body.len() > self.config.max_result_bytes {
+                log::warn!(
+                    "Dropping result of invocation {id}: {} bytes exceed the result store limit",
+                    body.len()
+                );
+
+                let error = ErrorResponse::new(
+                    ErrorStage::Result,
+                    format!(
+                        "Result has {} bytes, but \"max_result_bytes\" is {}",
+                        body.len(),
+                        self.config.max_result_bytes
+                    ),
+                );
+
+                let mut headers = HeaderMap::new();
+                headers.insert(
+                    header::CONTENT_TYPE,
+                    header::HeaderValue::from_static("application/json"),
+                );
+
+                (
+                    InvocationStatus::Failed,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    headers,
+                    Bytes::from(serde_json::to_vec(&error).expect("Failed to serialize error")),
+                )
+            } else {
+                (InvocationStatus::Completed, status, headers, body)
+            };
+
+        invocation.status = invocation_status;
+        invocation.finished_at = Some(Instant::now());
+        invocation.task = None;
+
+        let len = body.len();
+        invocation.result = Some(StoredResult {
+            status,
+            headers,
+            body,
+        });
+
+        table.finished.push_back(id.to_string());
+        table.result_bytes += len;
+
+        while table.result_bytes > self.config.max_result_bytes {
+            if !table.evict_oldest() {
+                break;
+            }
+        }
+    }
+
+    /// Stops a running invocation or discards the result of a finished one
+    ///
+    /// Returns the status of the invocation before it was cancelled.
+    pub fn cancel(&self, id: &str) -> Option<InvocationStatus> {
+        let mut table = self.table.lock();
+        self.remove_expired(&mut table);
+
+        let invocation = table.invocations.get_mut(id)?;
+        let status = invocation.status;
+
+        if status == InvocationStatus::Running {
+            if let Some(task) = invocation.task.take() {
+                task.abort();
+            }
+
+            // Keep the entry around, so clients can see it was cancelled
+            invocation.status = InvocationStatus::Cancelled;
+            invocation.finished_at = Some(Instant::now());
+            table.finished.push_back(id.to_string());
+        } else {
+            table.remove(id);
+        }
+
+        Some(status)
+    }
+
+    /// Number of invocations (running or finished) that are currently tracked
+    pub fn num_invocations(&self) -> usize {
+        self.table.lock().invocations.len()
+    }
+
+    /// Returns the name of the function an invocation belongs to
+    pub fn get_function(&self, id: &str) -> Option<String> {
+        let table = self.table.lock();
+        table
+            .invocations
+            .get(id)
+            .map(|invocation| invocation.function.clone())
+    }
+
+    /// Generates the response for a status request
+    ///
+    /// Finished invocations return the response of the function itself.
+    pub fn get_response(&self, id: &str) -> Option<http::Result<Response<Full<Bytes>>>> {
+        let mut table = self.table.lock();
+        self.remove_expired(&mut table);
+
+        let invocation = table.invocations.get(id)?;
+
+        if let Some(result) = &invocation.result {
+            let mut response = Response::builder().status(result.status);
+            for (name, value) in result.headers.iter() {
+                response = response.header(name, value);
+            }
+
+            return Some(response.body(result.body.clone().into()));
+        }
+
+        let info = InvocationInfo {
+            id: id.to_string(),
+            function: invocation.function.clone(),
+            status: invocation.status,
+            created_at: invocation.created_at,
+        };
+
+        let status = match invocation.status {
+            InvocationStatus::Running => StatusCode::ACCEPTED,
+            _ => StatusCode::OK,
+        };
+
+        let body = serde_json::to_vec(&info).expect("Failed to serialize invocation");
+
+        Some(
+            Response::builder()
+                .status(status)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(body.into()),
+        )
+    }
+
+    fn remove_expired(&self, table: &mut InvocationTable) {
+        let ttl = Duration::from_secs(self.config.result_ttl_secs);
+
+        while let Some(id) = table.finished.front() {
+            let expired = match table.invocations.get(id) {
+                Some(invocation) => invocation
+                    .finished_at
+                    .map(|finished_at| finished_at.elapsed() >= ttl)
+                    .unwrap_or(false),
+                None => true,
+            };
+
+            if !expired {
+                break;
+            }
+
+            table.evict_oldest();
+        }
+    }
+
+    /// Drops all results whose TTL has expired
+    pub fn cleanup(&self) {
+        let mut table = self.table.lock();
+        self.remove_expired(&mut table);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use wasmtime::ResourceLimiter;
+
+/// A guest tried to use more resources than it is allowed to
+#[derive(Debug)]
+pub enum LimitError {
+    Memory { desired: usize, limit: usize },
+    Table { desired: u32, limit: u32 },
+}
+
+impl LimitError {
+    /// Name of the resource, as used in metrics
+    pub fn resource(&self) -> &'static str {
+        match self {
+            Self::Memory { .. } => "memory",
+            Self::Table { .. } => "table",
+        }
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory { desired, limit } => write!(
+                f,
+                "Linear memory limit exceeded: requested {desired} bytes, but limit is {limit} bytes"
+            ),
+            Self::Table { desired, limit } => write!(
+                f,
+                "Table limit exceeded: requested {desired} elements, but limit is {limit} elements"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+/// Enforces the resource limits of a single instance
+///
+/// Limits that are not set fall back to those of the pooling allocator.
+#[derive(Clone, Debug, Default)]
+pub struct InstanceLimits {
+    pub max_memory_bytes: Option<usize>,
+    pub max_table_elements: Option<u32>,
+}
+
+impl ResourceLimiter for InstanceLimits {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        match self.max_memory_bytes {
+            Some(limit) if desired > limit => Err(LimitError::Memory { desired, limit }.into()),
+            _ => Ok(true),
+        }
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        desired: u32,
+        _maximum: Option<u32>,
+    ) -> anyhow::Result<bool> {
+        match self.max_table_elements {
+            Some(limit) if desired > limit => Err(LimitError::Table { desired, limit }.into()),
+            _ => Ok(true),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::io;
+use std::net::IpAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use anyhow::Context as _;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+
+use crate::address::WorkerAddr;
+
+/// Accepts client connections over TCP or a UNIX domain socket
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// A client connection accepted by a `Listener`
+pub enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Listener {
+    pub async fn bind(addr: &WorkerAddr) -> anyhow::Result<Self> {
+        match addr {
+            WorkerAddr::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("Failed to bind socket at {addr}"))?;
+                Ok(Self::Tcp(listener))
+            }
+            WorkerAddr::Unix(path) => {
+                remove_stale_socket(path)?;
+
+                let listener = UnixListener::bind(path.as_path())
+                    .with_context(|| format!("Failed to bind UNIX socket at {path:?}"))?;
+                Ok(Self::Unix(listener))
+            }
+        }
+    }
+
+    /// Waits for the next connection and returns it along with a description of the peer
+    pub async fn accept(&self) -> io::Result<(Connection, String)> {
+        match self {
+            Self::Tcp(listener) => {
+                let (conn, addr) = listener.accept().await?;
+                // Only affects latency, so there is no reason to drop the connection
+                if let Err(err) = conn.set_nodelay(true) {
+                    log::warn!("Failed to set TCP_NODELAY for connection from {addr}: {err}");
+                }
+                Ok((Connection::Tcp(conn), addr.to_string()))
+            }
+            Self::Unix(listener) => {
+                let (conn, addr) = listener.accept().await?;
+                Ok((Connection::Unix(conn), format!("{addr:?}")))
+            }
+        }
+    }
+}
+
+/// Removes a socket file left behind by a worker that did not shut down cleanly
+///
+/// Fails if the path is not a socket or another process is still listening on it.
+fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to inspect socket path {path:?}"))
+        }
+    };
+
+    if !metadata.file_type().is_socket() {
+        anyhow::bail!("Cannot listen at {path:?}: file exists and is not a socket");
+    }
+
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => anyhow::bail!("Cannot listen at {path:?}: socket is already in use"),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+            log::info!("Removing stale socket at {path:?}");
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove stale socket at {path:?}"))
+        }
+        Err(err) => Err(err).with_context(|| format!("Failed to check socket at {path:?}")),
+    }
+}
+
+impl Connection {
+    /// The IP address of the client (`None` for UNIX sockets)
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        match self {
+            Self::Tcp(conn) => conn.peer_addr().ok().map(|addr| addr.ip()),
+            Self::Unix(_) => None,
+        }
+    }
+}
+
+impl AsyncRead for Connection {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(conn) => Pin::new(conn).poll_read(cx, buf),
+            Self::Unix(conn) => Pin::new(conn).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Connection {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(conn) => Pin::new(conn).poll_write(cx, buf),
+            Self::Unix(conn) => Pin::new(conn).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Tcp(conn) => Pin::new(conn).poll_write_vectored(cx, bufs),
+            Self::Unix(conn) => Pin::new(conn).poll_write_vectored(cx, bufs),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        match self {
+            Self::Tcp(conn) => conn.is_write_vectored(),
+            Self::Unix(conn) => conn.is_write_vectored(),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(conn) => Pin::new(conn).poll_flush(cx),
+            Self::Unix(conn) => Pin::new(conn).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Tcp(conn) => Pin::new(conn).poll_shutdown(cx),
+            Self::Unix(conn) => Pin::new(conn).poll_shutdown(cx),
+        }
+    }
+}
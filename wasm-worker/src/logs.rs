@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::Engine;
+
+use dashmap::DashMap;
+
+use hyper::header::HeaderValue;
+
+use parking_lot::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// Returns the logs of a call (as base64-encoded JSON) if requested with `?logs=true`
+pub const LOGS_HEADER: &str = "x-ol-logs";
+
+/// How many bytes of (JSON-encoded) log lines are captured per invocation
+///
+/// Logs are returned in a header, so this needs to stay well below
+/// the header size limits of common HTTP clients.
+const MAX_INVOCATION_LOG_BYTES: usize = 16 * 1024;
+
+/// How many log lines are kept for each function
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub buffer_lines: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self { buffer_lines: 1000 }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Error,
+    Fatal,
+}
+
+/// Whether the client asked for the logs of a call with `?logs=true`
+pub fn is_requested(query: Option<&str>) -> bool {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .any(|param| param == "logs=true" || param == "logs=1")
+}
+
+/// Logs are kept per function, so strip the version or alias (e.g., `hashing@3`)
+fn base_name(function: &str) -> &str {
+    match function.split_once('@') {
+        Some((name, _)) => name,
+        None => function,
+    }
+}
+
+/// A message logged by a guest
+#[derive(Clone, Debug, Serialize)]
+pub struct LogLine {
+    pub timestamp_ms: u64,
+    pub level: LogLevel,
+    pub function: String,
+    pub invocation_id: String,
+    pub message: String,
+}
+
+impl LogLine {
+    pub fn new(level: LogLevel, function: &str, invocation_id: &str, message: &str) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            timestamp_ms,
+            level,
+            function: function.to_string(),
+            invocation_id: invocation_id.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// The log lines of a single invocation
+#[derive(Clone, Debug, Default)]
+pub struct InvocationLogs {
+    lines: Vec<LogLine>,
+    num_bytes: usize,
+    num_dropped: usize,
+}
+
+impl InvocationLogs {
+    /// Adds a line unless the invocation already logged too much
+    pub fn push(&mut self, line: LogLine) {
+        // Count the encoded size, so that the metadata of many short lines adds up as well
+        let len = serde_json::to_vec(&line)
+            .expect("Failed to serialize log line")
+            .len();
+
+        if self.num_bytes + len > MAX_INVOCATION_LOG_BYTES {
+            self.num_dropped += 1;
+            return;
+        }
+
+        self.num_bytes += len;
+        self.lines.push(line);
+    }
+
+    pub fn num_dropped(&self) -> usize {
+        self.num_dropped
+    }
+
+    /// Encodes the lines for the `X-OL-Logs` header
+    pub fn to_header_value(&self) -> HeaderValue {
+        let json = serde_json::to_vec(&self.lines).expect("Failed to serialize logs");
+        let encoded = base64::engine::general_purpose::STANDARD.encode(json);
+
+        HeaderValue::try_from(encoded).expect("base64 is always a valid header value")
+    }
+}
+
+/// Keeps the most recent log lines of every function
+pub struct LogStore {
+    max_lines: usize,
+    functions: DashMap<String, Mutex<VecDeque<LogLine>>>,
+}
+
+impl LogStore {
+    pub fn new(config: &LogConfig) -> Self {
+        Self {
+            max_lines: config.buffer_lines,
+            functions: Default::default(),
+        }
+    }
+
+    /// Appends the logs of an invocation, evicting the oldest lines if needed
+    ///
+    /// Lines of all versions and aliases of a function are kept together.
+    pub fn record(&self, function: &str, logs: &InvocationLogs) {
+        if logs.lines.is_empty() || self.max_lines == 0 {
+            return;
+        }
+
+        let entry = self
+            .functions
+            .entry(base_name(function).to_string())
+            .or_default();
+        let mut buffer = entry.lock();
+
+        for line in logs.lines.iter() {
+            if buffer.len() >= self.max_lines {
+                buffer.pop_front();
+            }
+            buffer.push_back(line.clone());
+        }
+    }
+
+    /// Returns the buffered lines of a function (across all versions), oldest first
+    pub fn get(&self, function: &str) -> Option<Vec<LogLine>> {
+        self.functions
+            .get(base_name(function))
+            .map(|buffer| buffer.lock().iter().cloned().collect())
+    }
+}
@@ -2,22 +2,26 @@
 
 use std::collections::HashMap;
 use std::fs::{read_dir, remove_file, File};
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::IpAddr;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread::available_parallelism;
+use std::time::{Duration, Instant};
 
 use http_body_util::{BodyExt, Full};
 
 use hyper::body::{Bytes, Incoming};
-use hyper::server::conn::http1;
+use hyper::header::{self, HeaderMap, HeaderValue};
 use hyper::{http, Method, Request, Response, StatusCode};
 
 use tokio::runtime;
 use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 use anyhow::Context;
 
+use wasmtime::Trap;
+
 use clap::Parser;
 
 mod support;
@@ -25,36 +29,173 @@ mod support;
 mod functions;
 use functions::FunctionManager;
 
-use parking_lot::Mutex;
-
 mod bindings;
+use bindings::args::ResultHandle;
+use bindings::request::RequestInfo;
 
 mod http_client;
 
+mod watcher;
+use watcher::RegistryWatcher;
+
+mod versions;
+use versions::VersionWeights;
+
+mod errors;
+use errors::{ErrorResponse, ErrorStage};
+
+mod stats;
+use stats::Stats;
+
+mod metrics;
+
+mod settings;
+use settings::{FunctionSettings, SettingsTable};
+
+mod limits;
+use limits::LimitError;
+
+mod concurrency;
+use concurrency::QueueError;
+
+mod drain;
+use drain::InFlightCalls;
+
+mod config;
+use config::{TlsConfig, WorkerConfig};
+
+mod server;
+use server::HttpProtocol;
+
+mod tls;
+use tls::TlsTerminator;
+
+mod address;
+use address::WorkerAddr;
+
+mod listener;
+use listener::Listener;
+
+mod invocations;
+use invocations::{InvocationStatus, InvocationStore};
+
+mod auth;
+use auth::{AuthError, Authenticator, Identity};
+
+mod ratelimit;
+use ratelimit::{Quota, RateLimitExceeded, RateLimiter};
+
+mod trace;
+use trace::{Span, SpanKind, TraceContext};
+
+mod logs;
+use logs::{InvocationLogs, LogStore, LOGS_HEADER};
+
+mod status;
+use status::{WorkerState, WorkerStatus};
+
+mod scheduler;
+use scheduler::{ScheduledRun, Scheduler};
+
+mod triggers;
+use triggers::{DirectoryTrigger, TriggeredFile};
+
+/// Created once the worker is ready and removed on shutdown (superseded by `GET /ready`)
+const READY_FILE: &str = "./ol-wasm.ready";
+
+/// How often expired results of asynchronous invocations and idle rate limit clients are removed
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait before accepting connections again after an error,
+/// e.g., because the worker ran out of file descriptors
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How many seconds clients should wait before retrying a rejected call
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// Reports which version of a function handled the call
+const VERSION_HEADER: &str = "x-ol-function-version";
+
+/// Allows clients to lower the timeout of a call
+const TIMEOUT_HEADER: &str = "x-ol-timeout-ms";
+
+/// Reports how much fuel a call consumed
+const FUEL_HEADER: &str = "x-ol-fuel-consumed";
+
+/// Identifies a call in the logs of the guest
+const INVOCATION_ID_HEADER: &str = "x-ol-invocation-id";
+
+/// Tells guests which schedule triggered a call
+const SCHEDULE_HEADER: &str = "x-ol-schedule";
+
+/// Tells guests which file triggered a call
+const TRIGGER_FILE_HEADER: &str = "x-ol-trigger-file";
+
+/// Reports the burst size of the rate limit that applies to a call
+const RATE_LIMIT_HEADER: &str = "x-ratelimit-limit";
+
+/// Reports how many more calls can be made right now
+const RATE_LIMIT_REMAINING_HEADER: &str = "x-ratelimit-remaining";
+
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    #[clap(long, short = 'l', default_value = "localhost:5000")]
-    #[clap(help = "What is the address to listen on for client requests?")]
-    listen_address: String,
+    #[clap(long, short = 'c')]
+    #[clap(help = "Path to a TOML or JSON config file (command line arguments take precedence)")]
+    config: Option<PathBuf>,
+
+    #[clap(long, short = 'l')]
+    #[clap(
+        help = "What is the address to listen on for client requests? Use unix:<path> for a UNIX socket [default: localhost:5000]"
+    )]
+    listen_address: Option<String>,
+
+    #[clap(long, short = 'p')]
+    #[clap(help = "Where are the WASM functions stored? [default: ./test-registry.wasm]")]
+    registry_path: Option<String>,
+
+    #[clap(long, value_enum)]
+    #[clap(help = "Which HTTP version to speak with clients [default: auto]")]
+    http_protocol: Option<HttpProtocol>,
+
+    #[clap(long)]
+    #[clap(help = "PEM file with the TLS certificate chain (enables TLS)")]
+    tls_cert_path: Option<PathBuf>,
+
+    #[clap(long)]
+    #[clap(help = "PEM file with the TLS private key")]
+    tls_key_path: Option<PathBuf>,
 
-    #[clap(long, short = 'p', default_value = "./test-registry.wasm")]
-    #[clap(help = "Where are the WASM functions stored?")]
-    registry_path: String,
+    #[clap(long)]
+    #[clap(
+        help = "PEM file with the CAs that client certificates must be signed by (enables mTLS)"
+    )]
+    tls_client_ca_path: Option<PathBuf>,
 
     #[clap(long)]
     enable_cpu_profiler: bool,
 
     #[clap(short = 'C')]
+    #[clap(help = "Config values for guests of the form <key>=<value>")]
     config_values: Option<Vec<String>>,
+
+    #[clap(short = 'S')]
+    #[clap(
+        help = "Per-function settings of the form <function>.<setting>=<value> (use '*' to set defaults)"
+    )]
+    function_settings: Option<Vec<String>>,
+
+    #[clap(long)]
+    #[clap(
+        help = "How long to wait for running calls to finish on shutdown (in milliseconds) [default: 30000]"
+    )]
+    shutdown_grace_period_ms: Option<u64>,
 }
 
 async fn load_functions(
     registry_path: &str,
     function_mgr: &Arc<FunctionManager>,
 ) -> anyhow::Result<()> {
-    let cache_path: PathBuf = format!("{registry_path}.cache").into();
-
     let directory = match read_dir(registry_path) {
         Ok(dir) => dir,
         Err(err) => {
@@ -84,18 +225,29 @@ async fn load_functions(
             continue;
         }
 
-        function_mgr
-            .load_function(file_path, cache_path.clone())
-            .await;
+        function_mgr.load_function(file_path).await?;
     }
 
     Ok(())
 }
 
+#[derive(Clone)]
 struct Service {
-    worker_addr: SocketAddr,
+    worker_addr: WorkerAddr,
     function_mgr: Arc<FunctionManager>,
     config_values: Arc<HashMap<String, String>>,
+    stats: Arc<Stats>,
+    in_flight: Arc<InFlightCalls>,
+    invocations: Arc<InvocationStore>,
+    /// Verifies clients, if authentication is enabled
+    auth: Option<Arc<Authenticator>>,
+    rate_limiter: Arc<RateLimiter>,
+    /// Recent log lines of every function
+    logs: Arc<LogStore>,
+    status: Arc<WorkerStatus>,
+    scheduler: Arc<Scheduler>,
+    /// The IP address of the client that opened the connection (if any)
+    client_ip: Option<IpAddr>,
 }
 
 impl hyper::service::Service<Request<Incoming>> for Service {
@@ -104,99 +256,971 @@ impl hyper::service::Service<Request<Incoming>> for Service {
     type Future = impl std::future::Future<Output = http::Result<Response<Full<Bytes>>>>;
 
     fn call(&self, req: Request<Incoming>) -> Self::Future {
-        Self::handle_request(
-            req,
-            self.worker_addr,
-            self.function_mgr.clone(),
-            self.config_values.clone(),
-        )
+        Self::handle_request(req, self.clone())
     }
 }
 
 impl Service {
     async fn handle_request(
         req: Request<Incoming>,
-        worker_addr: SocketAddr,
-        function_mgr: Arc<FunctionManager>,
-        config_values: Arc<HashMap<String, String>>,
+        service: Service,
     ) -> http::Result<Response<Full<Bytes>>> {
         log::trace!("Got new request: {req:?}");
 
-        let mut path = req
-            .uri()
+        let (mut parts, body) = req.into_parts();
+
+        // Calls by guests are not counted against the client limit a second time
+        let is_internal = ratelimit::take_internal_call_marker(&mut parts.headers);
+
+        let uri = &parts.uri;
+        let method = &parts.method;
+
+        let path = uri
             .path()
             .split('/')
             .filter(|x| !x.is_empty())
-            .map(String::from)
-            .collect::<Vec<String>>();
+            .collect::<Vec<&str>>();
+
+        // Check credentials before reading the body, so unauthenticated clients cannot make
+        // the worker buffer large requests
+        let identity = match path.as_slice() {
+            // Internal calls without forwarded credentials were started by the worker itself
+            // (schedules and triggers), so there is no client to authenticate
+            ["run", _, ..] if is_internal && !auth::has_credentials(&parts.headers) => Ok(None),
+            ["run", name, ..] | ["invoke-async", name] | ["logs", name] => {
+                service.authorize(&parts.headers, name)
+            }
+            ["functions"] => service.authorize_admin(&parts.headers, None),
+            ["functions", name, ..] => service.authorize_admin(&parts.headers, Some(name)),
+            _ => Ok(None),
+        };
+
+        let identity = match identity {
+            Ok(identity) => identity,
+            Err(err) => return err.into_response(),
+        };
 
-        let method = req.method().clone();
-        let args = req.collect().await.unwrap().to_bytes().to_vec();
+        let args = match body.collect().await {
+            Ok(body) => body.to_bytes().to_vec(),
+            Err(err) => {
+                return ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("Failed to read request body: {err}"),
+                )
+                .into_response(StatusCode::BAD_REQUEST);
+            }
+        };
 
-        if method == Method::POST && path.len() == 2 && path[0] == "run" {
-            Self::execute_function(
-                worker_addr,
-                &path.pop().unwrap(),
-                args,
-                function_mgr,
-                config_values,
+        let Service {
+            worker_addr,
+            function_mgr,
+            config_values,
+            stats,
+            in_flight,
+            invocations,
+            ..
+        } = service.clone();
+
+        // Functions might not have been loaded yet
+        if matches!(path.as_slice(), ["run", ..] | ["invoke-async", ..])
+            && service.status.state() == WorkerState::Starting
+        {
+            let mut response = ErrorResponse::new(ErrorStage::Routing, "Worker is still starting")
+                .into_response(StatusCode::SERVICE_UNAVAILABLE)?;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+            return Ok(response);
+        }
+
+        match path.as_slice() {
+            ["run", name, rest @ ..] => {
+                let quota = match service
+                    .check_rate_limits(name, identity.as_deref(), is_internal)
+                    .await
+                {
+                    Ok(quota) => quota,
+                    Err(err) => return Self::rate_limited(name, err),
+                };
+
+                let Some(_call) = in_flight.start() else {
+                    return ErrorResponse::new(ErrorStage::Routing, "Worker is shutting down")
+                        .into_response(StatusCode::SERVICE_UNAVAILABLE);
+                };
+
+                let mut span = Self::start_invocation_span(name, &parts.headers);
+                let invocation_id = uuid::Uuid::new_v4().to_string();
+                span.set_attribute("ol.invocation_id", invocation_id.clone());
+
+                let request = RequestInfo {
+                    function: name.to_string(),
+                    invocation_id: invocation_id.clone(),
+                    method: method.to_string(),
+                    path: rest.iter().flat_map(|part| ["/", part]).collect(),
+                    query: uri.query().unwrap_or_default().to_string(),
+                    headers: parts.headers.clone(),
+                    identity,
+                    trace: Some(*span.context()),
+                };
+
+                let mut response = Self::execute_function(
+                    &worker_addr,
+                    name,
+                    request,
+                    args,
+                    function_mgr,
+                    config_values,
+                    stats,
+                )
+                .await?;
+
+                span.set_attribute("http.status_code", response.status().as_u16());
+                span.end(!response.status().is_success());
+
+                if let Some(logs) = response.extensions_mut().remove::<InvocationLogs>() {
+                    service.logs.record(name, &logs);
+
+                    if logs::is_requested(uri.query()) {
+                        response
+                            .headers_mut()
+                            .insert(LOGS_HEADER, logs.to_header_value());
+                    }
+                }
+
+                response.headers_mut().insert(
+                    INVOCATION_ID_HEADER,
+                    HeaderValue::try_from(invocation_id).expect("UUIDs are valid header values"),
+                );
+
+                if let Some(quota) = quota {
+                    Self::set_quota_headers(response.headers_mut(), quota);
+                }
+
+                Ok(response)
+            }
+            ["invoke-async", name] if *method == Method::POST => {
+                let quota = match service
+                    .check_rate_limits(name, identity.as_deref(), is_internal)
+                    .await
+                {
+                    Ok(quota) => quota,
+                    Err(err) => return Self::rate_limited(name, err),
+                };
+
+                let span = Self::start_invocation_span(name, &parts.headers);
+
+                // The invocation ID is assigned once the call is queued
+                let request = RequestInfo {
+                    function: name.to_string(),
+                    invocation_id: String::new(),
+                    method: Method::POST.to_string(),
+                    path: String::new(),
+                    query: String::new(),
+                    headers: parts.headers,
+                    identity,
+                    trace: Some(*span.context()),
+                };
+
+                let mut response = Self::invoke_async(name, request, args, span, service).await?;
+
+                if let Some(quota) = quota {
+                    Self::set_quota_headers(response.headers_mut(), quota);
+                }
+
+                Ok(response)
+            }
+            ["invocations", id] if *method == Method::GET || *method == Method::DELETE => {
+                // Clients need access to the function to see or cancel its invocations
+                if let Some(function) = invocations.get_function(id) {
+                    if let Err(err) = service.authorize(&parts.headers, &function) {
+                        return err.into_response();
+                    }
+                }
+
+                if *method == Method::GET {
+                    Self::get_invocation(id, invocations).await
+                } else {
+                    Self::cancel_invocation(id, invocations).await
+                }
+            }
+            ["status"] if *method == Method::GET => {
+                Self::get_status(
+                    function_mgr,
+                    in_flight,
+                    invocations,
+                    service.rate_limiter,
+                    service.status,
+                )
+                .await
+            }
+            ["ready"] if *method == Method::GET => Self::get_ready(service.status).await,
+            ["schedules"] if *method == Method::GET => Self::get_schedules(service.scheduler).await,
+            ["stats"] if *method == Method::GET => Self::get_stats(function_mgr, stats).await,
+            ["metrics"] if *method == Method::GET => Self::get_metrics().await,
+            ["logs", name] if *method == Method::GET => {
+                Self::get_logs(name, function_mgr, service.logs).await
+            }
+            ["functions"] if *method == Method::GET => Self::list_functions(function_mgr).await,
+            ["functions", name] if *method == Method::PUT => {
+                Self::deploy_function(name, args, function_mgr).await
+            }
+            ["functions", name] if *method == Method::DELETE => {
+                Self::delete_function(name, function_mgr).await
+            }
+            ["functions", name, "aliases"] if *method == Method::GET => {
+                Self::list_aliases(name, function_mgr).await
+            }
+            ["functions", name, "aliases", alias] if *method == Method::PUT => {
+                Self::set_alias(name, alias, args, function_mgr).await
+            }
+            ["functions", name, "aliases", alias] if *method == Method::DELETE => {
+                Self::remove_alias(name, alias, function_mgr).await
+            }
+            ["invoke-async", _]
+            | ["invocations", _]
+            | ["status"]
+            | ["ready"]
+            | ["schedules"]
+            | ["stats"]
+            | ["metrics"]
+            | ["logs", _]
+            | ["functions"]
+            | ["functions", _]
+            | ["functions", _, "aliases"]
+            | ["functions", _, "aliases", _] => {
+                log::debug!("Got request with unsupported method {method} to {path:?}");
+
+                ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("Method {method} not allowed for \"{}\"", uri.path()),
+                )
+                .into_response(StatusCode::METHOD_NOT_ALLOWED)
+            }
+            _ => {
+                log::debug!("Got request to unknown path {path:?}");
+
+                ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("No such endpoint \"{}\"", uri.path()),
+                )
+                .into_response(StatusCode::NOT_FOUND)
+            }
+        }
+    }
+
+    /// Checks whether the client may invoke a function
+    ///
+    /// Returns the verified identity of the client, or `None` if authentication is disabled.
+    fn authorize(
+        &self,
+        headers: &HeaderMap,
+        function: &str,
+    ) -> Result<Option<Arc<Identity>>, AuthError> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+
+        match auth.authorize(headers, function) {
+            Ok(identity) => {
+                log::trace!("Authenticated \"{}\" for \"{function}\"", identity.subject);
+                Ok(Some(Arc::new(identity)))
+            }
+            Err(err) => {
+                log::debug!("Rejected call to \"{function}\": {err}");
+                Err(err)
+            }
+        }
+    }
+
+    /// Checks that the client may manage functions (or a specific one)
+    fn authorize_admin(
+        &self,
+        headers: &HeaderMap,
+        function: Option<&str>,
+    ) -> Result<Option<Arc<Identity>>, AuthError> {
+        let Some(auth) = &self.auth else {
+            return Ok(None);
+        };
+
+        match auth.authorize_admin(headers, function) {
+            Ok(identity) => {
+                log::trace!("Authenticated \"{}\" as admin", identity.subject);
+                Ok(Some(Arc::new(identity)))
+            }
+            Err(err) => {
+                log::debug!("Rejected management request: {err}");
+                Err(err)
+            }
+        }
+    }
+
+    /// Takes a token from the rate limits of the function and the client
+    ///
+    /// Clients are identified by their verified identity or, if there is none, their IP address.
+    /// Internal calls only count against the limit of the function.
+    async fn check_rate_limits(
+        &self,
+        name: &str,
+        identity: Option<&Identity>,
+        is_internal: bool,
+    ) -> Result<Option<Quota>, RateLimitExceeded> {
+        // Unknown functions are rejected later on
+        let Some(function) = self.function_mgr.get_function(name).await else {
+            return Ok(None);
+        };
+
+        let client = match (identity, self.client_ip) {
+            _ if is_internal => None,
+            (Some(identity), _) => Some(format!("id:{}", identity.subject)),
+            (None, Some(ip)) => Some(format!("ip:{ip}")),
+            (None, None) => None,
+        };
+
+        self.rate_limiter.check(
+            &function.info().name,
+            function.settings(),
+            client.as_deref(),
+        )
+    }
+
+    fn rate_limited(name: &str, err: RateLimitExceeded) -> http::Result<Response<Full<Bytes>>> {
+        let scope = err.scope.as_str();
+        log::debug!("Rejected call to \"{name}\": {scope} rate limit exceeded");
+
+        metrics::RATE_LIMITED
+            .with_label_values(&[name, scope])
+            .inc();
+
+        let mut response = ErrorResponse::new(
+            ErrorStage::RateLimit,
+            format!("Rate limit of the {scope} exceeded"),
+        )
+        .into_response(StatusCode::TOO_MANY_REQUESTS)?;
+
+        let retry_after = err.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+        let headers = response.headers_mut();
+        Self::set_quota_headers(
+            headers,
+            Quota {
+                limit: err.limit,
+                remaining: 0,
+            },
+        );
+        headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+
+        Ok(response)
+    }
+
+    fn set_quota_headers(headers: &mut HeaderMap, quota: Quota) {
+        headers.insert(RATE_LIMIT_HEADER, HeaderValue::from(quota.limit));
+        headers.insert(
+            RATE_LIMIT_REMAINING_HEADER,
+            HeaderValue::from(quota.remaining),
+        );
+    }
+
+    /// Starts the span covering an invocation
+    ///
+    /// Continues the trace of the client if the request has a valid `traceparent` header.
+    fn start_invocation_span(name: &str, headers: &HeaderMap) -> Span {
+        let parent = TraceContext::from_headers(headers);
+
+        let mut span = Span::start(format!("run {name}"), SpanKind::Server, parent.as_ref());
+        span.set_attribute("ol.function", name);
+        span
+    }
+
+    /// Starts a function call in the background and returns its invocation ID
+    async fn invoke_async(
+        name: &str,
+        mut request: RequestInfo,
+        args: Vec<u8>,
+        mut span: Span,
+        service: Service,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        if service.function_mgr.get_function(name).await.is_none() {
+            return ErrorResponse::new(ErrorStage::Routing, format!("No such function \"{name}\""))
+                .into_response(StatusCode::NOT_FOUND);
+        }
+
+        let Some(call) = service.in_flight.start() else {
+            return ErrorResponse::new(ErrorStage::Routing, "Worker is shutting down")
+                .into_response(StatusCode::SERVICE_UNAVAILABLE);
+        };
+
+        let Some(id) = service.invocations.create(name) else {
+            let mut response = ErrorResponse::new(
+                ErrorStage::Queue,
+                "Too many asynchronous invocations are still running",
             )
-            .await
-        } else if method == Method::GET && path.len() == 1 && path[0] == "status" {
-            Self::get_status().await
+            .into_response(StatusCode::TOO_MANY_REQUESTS)?;
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+            return Ok(response);
+        };
+
+        log::debug!("Starting asynchronous invocation {id} of \"{name}\"");
+        request.invocation_id.clone_from(&id);
+
+        let invocations = service.invocations.clone();
+        let task = {
+            let id = id.clone();
+            let name = name.to_string();
+
+            tokio::spawn(async move {
+                let _call = call;
+
+                let response = Self::execute_function(
+                    &service.worker_addr,
+                    &name,
+                    request,
+                    args,
+                    service.function_mgr,
+                    service.config_values,
+                    service.stats,
+                )
+                .await;
+
+                let (status, headers, body) = match response {
+                    Ok(response) => {
+                        let (mut parts, body) = response.into_parts();
+
+                        if let Some(logs) = parts.extensions.remove::<InvocationLogs>() {
+                            service.logs.record(&name, &logs);
+                        }
+
+                        let body = body.collect().await.expect("Infallible").to_bytes();
+                        (parts.status, parts.headers, body)
+                    }
+                    Err(err) => {
+                        log::error!("Failed to generate response for invocation {id}: {err}");
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            HeaderMap::new(),
+                            Bytes::new(),
+                        )
+                    }
+                };
+
+                log::debug!("Asynchronous invocation {id} finished with status {status}");
+
+                span.set_attribute("ol.invocation_id", id.clone());
+                span.set_attribute("http.status_code", status.as_u16());
+                span.end(!status.is_success());
+
+                service.invocations.complete(&id, status, headers, body);
+            })
+        };
+
+        invocations.set_task(&id, task.abort_handle());
+
+        let body = serde_json::to_vec(&serde_json::json!({ "id": id, "function": name }))
+            .expect("Failed to serialize invocation");
+
+        Response::builder()
+            .status(StatusCode::ACCEPTED)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::LOCATION, format!("/invocations/{id}"))
+            .body(body.into())
+    }
+
+    /// Starts scheduled calls when they are due, until the worker shuts down
+    async fn run_scheduler(service: Service) {
+        let scheduler = service.scheduler.clone();
+
+        while let Some(delay) = scheduler.time_until_next_run() {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+                continue;
+            }
+
+            for run in scheduler.start_due_runs() {
+                tokio::spawn(Self::execute_scheduled(run, service.clone()));
+            }
+        }
+
+        log::info!("No more scheduled runs");
+    }
+
+    async fn execute_scheduled(run: ScheduledRun, service: Service) {
+        let schedule = run.schedule();
+
+        let Some(_call) = service.in_flight.start() else {
+            log::debug!(
+                "Not starting schedule \"{}\"; worker is shutting down",
+                schedule.name
+            );
+            return;
+        };
+
+        let invocation_id = uuid::Uuid::new_v4().to_string();
+        log::debug!(
+            "Starting scheduled run {invocation_id} of \"{}\" ({})",
+            schedule.function,
+            schedule.name
+        );
+
+        // Schedule names are validated, so they are always valid header values
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            SCHEDULE_HEADER,
+            HeaderValue::try_from(schedule.name.as_str()).expect("Invalid schedule name"),
+        );
+
+        let mut span = Self::start_internal_span(&schedule.function, &invocation_id);
+        span.set_attribute("ol.schedule", schedule.name.clone());
+
+        let (status, _) = Self::execute_internal(
+            &schedule.function,
+            &invocation_id,
+            schedule.args.clone(),
+            headers,
+            span,
+            &service,
+        )
+        .await;
+
+        let outcome = if status.is_success() {
+            log::debug!("Scheduled run {invocation_id} finished");
+            "success"
         } else {
-            panic!("Got unexpected request to {path:?} (Method: {method:?})");
+            log::warn!(
+                "Scheduled run {invocation_id} of \"{}\" failed with status {status}",
+                schedule.name
+            );
+            "error"
+        };
+
+        metrics::SCHEDULED_RUNS
+            .with_label_values(&[&schedule.name, outcome])
+            .inc();
+
+        run.finish(invocation_id, status);
+    }
+
+    /// Invokes the trigger's function for every new file in its directory
+    async fn run_directory_trigger(mut trigger: DirectoryTrigger, service: Service) {
+        let permits = Arc::new(Semaphore::new(trigger.config().max_concurrency));
+
+        loop {
+            // Held while the file is read and its call runs
+            let permit = permits
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("Semaphore was closed");
+
+            let Some(file) = trigger.next_file().await else {
+                break;
+            };
+            let config = trigger.config();
+
+            let args = match file.get_args(config) {
+                Ok(args) => args,
+                Err(err) => {
+                    log::warn!("Cannot process {:?}: {err:#}", file.path());
+                    metrics::FILE_TRIGGERS
+                        .with_label_values(&[&config.function, "failed"])
+                        .inc();
+                    file.finish(false);
+                    continue;
+                }
+            };
+
+            let function = config.function.clone();
+            tokio::spawn(Self::process_file(
+                file,
+                function,
+                args,
+                service.clone(),
+                permit,
+            ));
         }
     }
 
+    /// Calls that were rejected (429 or 503) or could not get an instance leave the file in
+    /// place, so that it is retried later
+    async fn process_file(
+        file: TriggeredFile,
+        function: String,
+        args: Vec<u8>,
+        service: Service,
+        _permit: OwnedSemaphorePermit,
+    ) {
+        let Some(_call) = service.in_flight.start() else {
+            log::debug!("Not processing {:?}; worker is shutting down", file.path());
+            return;
+        };
+
+        let invocation_id = uuid::Uuid::new_v4().to_string();
+        log::debug!(
+            "Invoking \"{function}\" for {:?} ({invocation_id})",
+            file.path()
+        );
+
+        let mut headers = HeaderMap::new();
+        if let Some(value) = file
+            .path()
+            .to_str()
+            .and_then(|path| HeaderValue::try_from(path).ok())
+        {
+            headers.insert(TRIGGER_FILE_HEADER, value);
+        }
+
+        let mut span = Self::start_internal_span(&function, &invocation_id);
+        span.set_attribute("ol.file", file.path().to_string_lossy().into_owned());
+
+        let (status, stage) =
+            Self::execute_internal(&function, &invocation_id, args, headers, span, &service).await;
+
+        let outcome = match (status, stage) {
+            (status, _) if status.is_success() => {
+                log::debug!("Processed {:?}", file.path());
+                file.finish(true);
+                "done"
+            }
+            // Instantiation fails, e.g., if the instance pool is exhausted
+            (StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE, _)
+            | (_, Some(ErrorStage::Instantiate | ErrorStage::Limit)) => {
+                log::debug!(
+                    "Call for {:?} was rejected with status {status}; retrying later",
+                    file.path()
+                );
+                "retry"
+            }
+            (status, _) => {
+                log::warn!(
+                    "Failed to process {:?}: \"{function}\" returned status {status}",
+                    file.path()
+                );
+                file.finish(false);
+                "failed"
+            }
+        };
+
+        metrics::FILE_TRIGGERS
+            .with_label_values(&[&function, outcome])
+            .inc();
+    }
+
+    /// Starts the span of a call that was not requested by a client
+    fn start_internal_span(function: &str, invocation_id: &str) -> Span {
+        let mut span = Span::start(format!("run {function}"), SpanKind::Server, None);
+        span.set_attribute("ol.function", function.to_string());
+        span.set_attribute("ol.invocation_id", invocation_id.to_string());
+        span
+    }
+
+    /// Runs a call that was not requested by a client, such as a scheduled run
+    ///
+    /// Returns the status code of the response and, if the worker generated an error, its stage.
+    async fn execute_internal(
+        function: &str,
+        invocation_id: &str,
+        args: Vec<u8>,
+        headers: HeaderMap,
+        mut span: Span,
+        service: &Service,
+    ) -> (StatusCode, Option<ErrorStage>) {
+        let request = RequestInfo {
+            function: function.to_string(),
+            invocation_id: invocation_id.to_string(),
+            method: Method::POST.to_string(),
+            path: String::new(),
+            query: String::new(),
+            headers,
+            identity: None,
+            trace: Some(*span.context()),
+        };
+
+        let response = Self::execute_function(
+            &service.worker_addr,
+            function,
+            request,
+            args,
+            service.function_mgr.clone(),
+            service.config_values.clone(),
+            service.stats.clone(),
+        )
+        .await;
+
+        let (status, stage) = match response {
+            Ok(mut response) => {
+                if let Some(logs) = response.extensions_mut().remove::<InvocationLogs>() {
+                    service.logs.record(function, &logs);
+                }
+                (
+                    response.status(),
+                    response.extensions().get::<ErrorStage>().copied(),
+                )
+            }
+            Err(err) => {
+                log::error!("Failed to generate response for invocation {invocation_id}: {err}");
+                (StatusCode::INTERNAL_SERVER_ERROR, None)
+            }
+        };
+
+        span.set_attribute("http.status_code", status.as_u16());
+        span.end(!status.is_success());
+
+        (status, stage)
+    }
+
+    async fn get_invocation(
+        id: &str,
+        invocations: Arc<InvocationStore>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        match invocations.get_response(id) {
+            Some(response) => response,
+            None => ErrorResponse::new(ErrorStage::Routing, format!("No such invocation \"{id}\""))
+                .into_response(StatusCode::NOT_FOUND),
+        }
+    }
+
+    async fn cancel_invocation(
+        id: &str,
+        invocations: Arc<InvocationStore>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let previous_status = match invocations.cancel(id) {
+            Some(status) => status,
+            None => {
+                return ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("No such invocation \"{id}\""),
+                )
+                .into_response(StatusCode::NOT_FOUND);
+            }
+        };
+
+        if previous_status == InvocationStatus::Running {
+            log::debug!("Cancelled asynchronous invocation {id}");
+        }
+
+        let body = serde_json::to_vec(&serde_json::json!({
+            "id": id,
+            "previous_status": previous_status,
+        }))
+        .expect("Failed to serialize invocation");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    /// Determines the timeout for a call from the function's settings
+    /// and the request's headers; the header can only lower the timeout
+    fn get_timeout(
+        settings: &FunctionSettings,
+        headers: &HeaderMap,
+    ) -> Result<Option<Duration>, String> {
+        let Some(value) = headers.get(TIMEOUT_HEADER) else {
+            return Ok(settings.timeout);
+        };
+
+        let millis: u64 = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| format!("Invalid value for header \"{TIMEOUT_HEADER}\""))?;
+        let timeout = Duration::from_millis(millis);
+
+        Ok(Some(match settings.timeout {
+            Some(max_timeout) => timeout.min(max_timeout),
+            None => timeout,
+        }))
+    }
+
     async fn execute_function(
-        worker_addr: SocketAddr,
+        worker_addr: &WorkerAddr,
         name: &str,
+        request: RequestInfo,
         args: Vec<u8>,
         function_mgr: Arc<FunctionManager>,
         config_values: Arc<HashMap<String, String>>,
+        stats: Arc<Stats>,
     ) -> http::Result<Response<Full<Bytes>>> {
-        let result = Arc::new(Mutex::new(None));
+        let result: ResultHandle = Default::default();
 
         let function = match function_mgr.get_function(name).await {
             Some(func) => func,
-            None => panic!("No such function \"{name}\""),
+            None => {
+                return ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("No such function \"{name}\""),
+                )
+                .into_response(StatusCode::NOT_FOUND);
+            }
+        };
+
+        let timeout = match Self::get_timeout(function.settings(), &request.headers) {
+            Ok(timeout) => timeout,
+            Err(msg) => {
+                return ErrorResponse::new(ErrorStage::Routing, msg)
+                    .into_response(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        // Held until the call has finished
+        let _slot = match function.acquire_slot().await {
+            Ok(slot) => slot,
+            Err(err) => {
+                let (status, msg) = match err {
+                    QueueError::Full => (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        format!("Too many pending calls for function \"{name}\""),
+                    ),
+                    QueueError::Timeout => (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        format!("Timed out waiting for a slot for function \"{name}\""),
+                    ),
+                };
+
+                log::debug!("{msg}");
+
+                let mut response =
+                    ErrorResponse::new(ErrorStage::Queue, msg).into_response(status)?;
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+
+                return Ok(response);
+            }
         };
 
         log::trace!("Starting function call for \"{name}\"");
 
-        let mut instance_hdl = function
-            .get_idle_instance(args, &config_values, worker_addr, result.clone())
-            .await;
+        let start = Instant::now();
+        let function_stats = stats.get(&function.info().id().to_string());
+
+        let mut instance_hdl = match function
+            .get_idle_instance(args, request, &config_values, worker_addr, result.clone())
+            .await
+        {
+            Ok(hdl) => hdl,
+            Err(err) => {
+                log::error!("Failed to instantiate function \"{name}\": {err:?}");
+                function_stats.record_invocation(start.elapsed(), false);
+
+                // The initial memory or table size might already exceed the limits
+                let stage = if let Some(limit_err) = err.downcast_ref::<LimitError>() {
+                    metrics::LIMIT_VIOLATIONS
+                        .with_label_values(&[
+                            &function.info().id().to_string(),
+                            limit_err.resource(),
+                        ])
+                        .inc();
+                    ErrorStage::Limit
+                } else {
+                    ErrorStage::Instantiate
+                };
+
+                return ErrorResponse::new(stage, format!("{err:#}"))
+                    .into_response(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        function_stats.record_start(instance_hdl.is_cold_start());
+        instance_hdl.set_timeout(timeout);
+
+        let settings = function.settings();
+        if let Err(err) = instance_hdl.set_fuel(settings.fuel, settings.fuel_yield_interval) {
+            instance_hdl.discard();
+            function_stats.record_invocation(start.elapsed(), false);
+
+            return ErrorResponse::new(
+                ErrorStage::Instantiate,
+                format!("Failed to set fuel: {err}"),
+            )
+            .into_response(StatusCode::INTERNAL_SERVER_ERROR);
+        }
 
         let (mut store, instance) = instance_hdl.get();
 
-        let call_result = instance
-            .get_func(&mut store, "f")
-            .unwrap()
-            .call_async(store, &[], &mut [])
-            .await;
+        let Some(entry_fn) = instance.get_func(&mut store, "f") else {
+            instance_hdl.discard();
+            function_stats.record_invocation(start.elapsed(), false);
+
+            return ErrorResponse::new(
+                ErrorStage::Instantiate,
+                format!("Function \"{name}\" does not export \"f\""),
+            )
+            .into_response(StatusCode::INTERNAL_SERVER_ERROR);
+        };
+
+        // The epoch deadline interrupts the guest while it is running, but not
+        // while it waits for a host call, so we need both mechanisms here.
+        // `None` indicates that the call did not finish in time.
+        let call_result = {
+            let _timer = metrics::EXECUTION_TIME
+                .with_label_values(&[&function.info().id().to_string()])
+                .start_timer();
+            let call = entry_fn.call_async(store, &[], &mut []);
+
+            if let Some(timeout) = timeout {
+                tokio::time::timeout(timeout, call).await.ok()
+            } else {
+                Some(call.await)
+            }
+        };
+
+        let timed_out = match &call_result {
+            None => true,
+            Some(Err(error)) => matches!(error.downcast_ref::<Trap>(), Some(Trap::Interrupt)),
+            Some(Ok(())) => false,
+        };
+
+        // Logs are kept even if the call failed, as they might explain why
+        let logs = std::mem::take(&mut result.lock().logs);
+
+        let fuel_consumed = instance_hdl.get_fuel_consumed();
+        function_stats.record_fuel(fuel_consumed);
+        function_stats.record_invocation(start.elapsed(), matches!(call_result, Some(Ok(()))));
+
+        let mut response = if timed_out {
+            let timeout_ms = timeout.unwrap_or_default().as_millis();
+            log::warn!("Function \"{name}\" did not finish within {timeout_ms}ms");
 
-        let response = if let Err(error) = call_result {
+            // The guest was interrupted at an arbitrary point, so do not reuse the instance
+            instance_hdl.discard();
+
+            ErrorResponse::new(
+                ErrorStage::Timeout,
+                format!("Function \"{name}\" did not finish within {timeout_ms}ms"),
+            )
+            .into_response(StatusCode::GATEWAY_TIMEOUT)?
+        } else if let Some(Err(error)) = call_result {
             // Handle a regular crash here
             log::error!("Function failed with message \"{}\"", error.root_cause());
 
-            let response = Response::builder()
-                .status(StatusCode::INTERNAL_SERVER_ERROR)
-                .body(Default::default())?;
-            instance_hdl.mark_idle();
+            let response = ErrorResponse::from_call_error(&error)
+                .into_response(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+            if let Some(limit_err) = error.downcast_ref::<LimitError>() {
+                metrics::LIMIT_VIOLATIONS
+                    .with_label_values(&[&function.info().id().to_string(), limit_err.resource()])
+                    .inc();
+                instance_hdl.discard();
+            } else if let Some(Trap::OutOfFuel) = error.downcast_ref::<Trap>() {
+                // Like with timeouts, the guest stopped at an arbitrary point
+                instance_hdl.discard();
+            } else {
+                instance_hdl.mark_idle();
+            }
 
             response
         } else {
-            let result = result.lock().take();
+            let result = std::mem::take(&mut *result.lock());
 
-            let body = if let Some(result) = result {
-                result.into()
-            } else {
-                vec![].into()
-            };
+            let mut response = Response::builder()
+                .status(result.status.unwrap_or(StatusCode::OK))
+                .body(result.body.unwrap_or_default().into())?;
 
-            let response = Response::builder().status(StatusCode::OK).body(body)?;
+            for (name, value) in result.headers {
+                response.headers_mut().append(name, value);
+            }
 
             instance_hdl.mark_idle();
             log::trace!("Done with function call for \"{name}\"");
@@ -204,58 +1228,359 @@ impl Service {
             response
         };
 
+        response
+            .headers_mut()
+            .insert(FUEL_HEADER, HeaderValue::from(fuel_consumed));
+
+        if let Some(version) = function.info().version {
+            response
+                .headers_mut()
+                .insert(VERSION_HEADER, HeaderValue::from(version));
+        }
+
+        if logs.num_dropped() > 0 {
+            log::warn!(
+                "Dropped {} log lines of function \"{name}\"",
+                logs.num_dropped()
+            );
+        }
+
+        response.extensions_mut().insert(logs);
+
         Ok(response)
     }
 
-    async fn get_status() -> http::Result<Response<Full<Bytes>>> {
+    async fn list_functions(
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let functions = function_mgr.list_functions();
+        let body = serde_json::to_vec(&functions).expect("Failed to serialize function list");
+
         Response::builder()
             .status(StatusCode::OK)
-            .body(vec![].into())
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
     }
-}
 
-async fn main_func(args: Args) -> anyhow::Result<()> {
-    let worker_addr: SocketAddr = match args.listen_address.to_socket_addrs() {
-        Ok(mut addrs) => addrs.next().unwrap(),
-        Err(err) => {
-            anyhow::bail!(
-                "Failed to parse listen address \"{}\": {err}",
-                args.listen_address
-            );
+    async fn deploy_function(
+        name: &str,
+        code: Vec<u8>,
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        match function_mgr.deploy_function(name, &code).await {
+            Ok(info) => {
+                log::info!("Deployed function \"{name}\"");
+                let body = serde_json::to_vec(&info).expect("Failed to serialize function info");
+
+                Response::builder()
+                    .status(StatusCode::OK)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body.into())
+            }
+            Err(err) => {
+                log::warn!("Failed to deploy function \"{name}\": {err:?}");
+
+                ErrorResponse::new(ErrorStage::Deploy, format!("{err:#}"))
+                    .into_response(StatusCode::BAD_REQUEST)
+            }
         }
+    }
+
+    async fn delete_function(
+        name: &str,
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        match function_mgr.delete_function(name) {
+            Ok(true) => Response::builder()
+                .status(StatusCode::OK)
+                .body(vec![].into()),
+            Ok(false) => {
+                ErrorResponse::new(ErrorStage::Routing, format!("No such function \"{name}\""))
+                    .into_response(StatusCode::NOT_FOUND)
+            }
+            Err(err) => ErrorResponse::new(ErrorStage::Deploy, format!("{err:#}"))
+                .into_response(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    async fn list_aliases(
+        name: &str,
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let aliases = function_mgr.get_aliases(name);
+        let body = serde_json::to_vec(&aliases).expect("Failed to serialize aliases");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    async fn set_alias(
+        name: &str,
+        alias: &str,
+        body: Vec<u8>,
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let weights: VersionWeights = match serde_json::from_slice(&body) {
+            Ok(weights) => weights,
+            Err(err) => {
+                return ErrorResponse::new(
+                    ErrorStage::Deploy,
+                    format!("Invalid version weights: {err}"),
+                )
+                .into_response(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        match function_mgr.set_alias(name, alias, weights) {
+            Ok(()) => Response::builder()
+                .status(StatusCode::OK)
+                .body(vec![].into()),
+            Err(err) => ErrorResponse::new(ErrorStage::Deploy, format!("{err:#}"))
+                .into_response(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    async fn remove_alias(
+        name: &str,
+        alias: &str,
+        function_mgr: Arc<FunctionManager>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        match function_mgr.remove_alias(name, alias) {
+            Ok(true) => Response::builder()
+                .status(StatusCode::OK)
+                .body(vec![].into()),
+            Ok(false) => {
+                ErrorResponse::new(ErrorStage::Routing, format!("No such alias \"{alias}\""))
+                    .into_response(StatusCode::NOT_FOUND)
+            }
+            Err(err) => ErrorResponse::new(ErrorStage::Deploy, format!("{err:#}"))
+                .into_response(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    async fn get_logs(
+        name: &str,
+        function_mgr: Arc<FunctionManager>,
+        logs: Arc<LogStore>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let exists = function_mgr.get_function(name).await.is_some();
+
+        // Logs of removed functions stay available, but versions and aliases must exist
+        let lines = match logs.get(name) {
+            Some(lines) if exists || !name.contains('@') => lines,
+            _ if exists => vec![],
+            _ => {
+                return ErrorResponse::new(
+                    ErrorStage::Routing,
+                    format!("No such function \"{name}\""),
+                )
+                .into_response(StatusCode::NOT_FOUND);
+            }
+        };
+
+        let body = serde_json::to_vec(&lines).expect("Failed to serialize logs");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    async fn get_schedules(scheduler: Arc<Scheduler>) -> http::Result<Response<Full<Bytes>>> {
+        let body = serde_json::to_vec(&scheduler.status()).expect("Failed to serialize schedules");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    async fn get_stats(
+        function_mgr: Arc<FunctionManager>,
+        stats: Arc<Stats>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let snapshot = stats.snapshot(&function_mgr);
+        let body = serde_json::to_vec(&snapshot).expect("Failed to serialize stats");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    async fn get_metrics() -> http::Result<Response<Full<Bytes>>> {
+        match metrics::encode() {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(header::CONTENT_TYPE, metrics::content_type())
+                .body(body.into()),
+            Err(err) => ErrorResponse::new(
+                ErrorStage::Routing,
+                format!("Failed to encode metrics: {err}"),
+            )
+            .into_response(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    async fn get_status(
+        function_mgr: Arc<FunctionManager>,
+        in_flight: Arc<InFlightCalls>,
+        invocations: Arc<InvocationStore>,
+        rate_limiter: Arc<RateLimiter>,
+        status: Arc<WorkerStatus>,
+    ) -> http::Result<Response<Full<Bytes>>> {
+        let report = status.report(&function_mgr, &in_flight, &invocations, &rate_limiter);
+        let body = serde_json::to_vec(&report).expect("Failed to serialize status");
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.into())
+    }
+
+    /// Succeeds once all functions have been loaded, and until the worker starts shutting down
+    async fn get_ready(status: Arc<WorkerStatus>) -> http::Result<Response<Full<Bytes>>> {
+        let msg = match status.state() {
+            WorkerState::Ready => {
+                return Response::builder()
+                    .status(StatusCode::OK)
+                    .body(vec![].into());
+            }
+            WorkerState::Starting => "Worker is still starting",
+            WorkerState::ShuttingDown => "Worker is shutting down",
+        };
+
+        ErrorResponse::new(ErrorStage::Routing, msg).into_response(StatusCode::SERVICE_UNAVAILABLE)
+    }
+}
+
+/// Loads the config file (if any) and applies command line arguments on top of it
+fn load_config(args: Args) -> anyhow::Result<(WorkerConfig, SettingsTable)> {
+    let mut config = match &args.config {
+        Some(path) => WorkerConfig::load(path)?,
+        None => WorkerConfig::default(),
     };
 
-    let function_mgr = Arc::new(
-        FunctionManager::new()
-            .await
-            .with_context(|| "Failed to create function manager")?,
-    );
+    if let Some(listen_address) = args.listen_address {
+        config.listen_address = listen_address;
+    }
 
-    let mut config_values = HashMap::default();
+    if let Some(registry_path) = args.registry_path {
+        config.registry_path = registry_path;
+    }
 
-    if let Some(vals) = args.config_values {
-        for entry in vals {
-            let mut split = entry.split('=');
-            let key = split.next().expect("Invalid config setting");
-            let value = split.next().expect("Invalid config setting");
+    if let Some(protocol) = args.http_protocol {
+        config.http_protocol = protocol;
+    }
 
-            config_values.insert(key.to_string(), value.to_string());
+    match (args.tls_cert_path, args.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            // Keep the other TLS options of the config file
+            config.tls = Some(match config.tls.take() {
+                Some(tls) => TlsConfig {
+                    cert_path,
+                    key_path,
+                    ..tls
+                },
+                None => TlsConfig {
+                    cert_path,
+                    key_path,
+                    client_ca_path: None,
+                    handshake_timeout_ms: config::default_handshake_timeout_ms(),
+                },
+            });
         }
+        (None, None) => {}
+        _ => anyhow::bail!("--tls-cert-path and --tls-key-path must be used together"),
     }
 
-    let config_values = Arc::new(config_values);
+    if let Some(path) = args.tls_client_ca_path {
+        match &mut config.tls {
+            Some(tls) => tls.client_ca_path = Some(path),
+            None => anyhow::bail!("--tls-client-ca-path requires a TLS certificate and key"),
+        }
+    }
 
-    load_functions(&args.registry_path, &function_mgr).await?;
+    if let Some(grace_period) = args.shutdown_grace_period_ms {
+        config.shutdown_grace_period_ms = grace_period;
+    }
 
-    let listener = tokio::net::TcpListener::bind(&worker_addr)
-        .await
-        .unwrap_or_else(|err| {
-            panic!("Failed to bind socket for OL wasm-worker at {worker_addr}: {err}")
-        });
+    let config_values = config::parse_config_values(&args.config_values.unwrap_or_default())
+        .with_context(|| "Invalid config values")?;
+    config.config_values.extend(config_values);
 
+    let mut settings = config.function_settings()?;
+    settings
+        .update(&args.function_settings.unwrap_or_default())
+        .with_context(|| "Invalid function settings")?;
+
+    Ok((config, settings))
+}
+
+async fn main_func(args: Args) -> anyhow::Result<()> {
     #[cfg(feature = "cpuprofiler")]
     let enable_cpu_profiler = args.enable_cpu_profiler;
 
+    let (config, settings) = load_config(args)?;
+
+    let worker_addr = WorkerAddr::parse(&config.listen_address)?;
+
+    let function_mgr = Arc::new(
+        FunctionManager::new(
+            &config.registry_path,
+            &config.engine,
+            &config.pooling,
+            settings,
+        )
+        .await
+        .with_context(|| "Failed to create function manager")?,
+    );
+
+    let config_values = Arc::new(config.config_values);
+    let stats = Arc::new(Stats::default());
+    let in_flight = Arc::new(InFlightCalls::default());
+    let invocations = Arc::new(InvocationStore::new(config.invocations));
+
+    trace::init(&config.tracing).with_context(|| "Failed to set up tracing")?;
+
+    let auth =
+        match Authenticator::new(config.auth).with_context(|| "Failed to set up authentication")? {
+            Some(auth) => {
+                log::info!("Authentication enabled for function calls");
+                Some(Arc::new(auth))
+            }
+            None => None,
+        };
+
+    let rate_limiter =
+        Arc::new(RateLimiter::new(&config.rate_limits).with_context(|| "Invalid rate limits")?);
+
+    {
+        let invocations = invocations.clone();
+        let rate_limiter = rate_limiter.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                invocations.cleanup();
+                rate_limiter.remove_idle_clients();
+            }
+        });
+    }
+
+    let tls = match config.tls {
+        Some(tls_config) => Some(Arc::new(
+            TlsTerminator::new(tls_config).with_context(|| "Failed to set up TLS")?,
+        )),
+        None => None,
+    };
+
+    let listener = Listener::bind(&worker_addr)
+        .await
+        .with_context(|| "Failed to start OL wasm-worker")?;
+
     #[cfg(feature = "cpuprofiler")]
     if enable_cpu_profiler {
         let mut profiler = cpuprofiler::PROFILER.lock().unwrap();
@@ -267,43 +1592,120 @@ async fn main_func(args: Args) -> anyhow::Result<()> {
         log::info!("CPU profiler enabled. Writing output to '{fname}'");
     }
 
-    let fut = tokio::spawn(async move {
-        while let Ok((conn, addr)) = listener.accept().await {
+    let status = Arc::new(WorkerStatus::new());
+    let scheduler =
+        Arc::new(Scheduler::new(&config.schedules).with_context(|| "Invalid schedules")?);
+
+    let service = Service {
+        worker_addr: worker_addr.clone(),
+        function_mgr: function_mgr.clone(),
+        config_values,
+        stats,
+        in_flight: in_flight.clone(),
+        invocations,
+        auth,
+        rate_limiter,
+        logs: Arc::new(LogStore::new(&config.logs)),
+        status: status.clone(),
+        scheduler,
+        client_ip: None,
+    };
+
+    let scheduler_service = service.clone();
+
+    let directory_triggers = config
+        .directory_triggers
+        .into_iter()
+        .map(DirectoryTrigger::new)
+        .collect::<anyhow::Result<Vec<_>>>()
+        .with_context(|| "Failed to set up directory triggers")?;
+
+    let http_protocol = config.http_protocol;
+    let service_tls = tls.clone();
+    let mut fut = tokio::spawn(async move {
+        loop {
+            let (conn, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    log::error!("Failed to accept connection: {err}");
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    continue;
+                }
+            };
+
             log::debug!("Got new connection from {addr}");
 
-            let function_mgr = function_mgr.clone();
-            let config_values = config_values.clone();
+            let mut service = service.clone();
+            service.client_ip = conn.peer_ip();
+            let tls = service_tls.clone();
 
             tokio::spawn(async move {
-                let service = Service {
-                    worker_addr,
-                    function_mgr,
-                    config_values,
+                let result = match tls {
+                    Some(tls) => match tls.accept(conn).await {
+                        Ok((stream, alpn_protocol)) => {
+                            let protocol = alpn_protocol.unwrap_or(http_protocol);
+                            server::serve_connection(stream, protocol, service).await
+                        }
+                        Err(err) => {
+                            log::warn!("TLS handshake with {addr} failed: {err}");
+                            return;
+                        }
+                    },
+                    None => server::serve_connection(conn, http_protocol, service).await,
                 };
 
-                conn.set_nodelay(true).unwrap();
-                let conn = support::TokioIo::new(conn);
-
-                if let Err(http_err) = http1::Builder::new()
-                    .keep_alive(true)
-                    .serve_connection(conn, service)
-                    .await
-                {
+                if let Err(http_err) = result {
                     log::error!("Error while serving HTTP connection: {http_err}");
                 }
             });
         }
     });
 
-    log::info!("Listening on http://{worker_addr}");
+    if let Some(tls) = tls {
+        log::info!("Listening on {worker_addr} (TLS)");
+
+        let mut sighup = signal(SignalKind::hangup()).expect("Failed to install sighandler");
+        tokio::spawn(async move {
+            while sighup.recv().await.is_some() {
+                match tls.reload() {
+                    Ok(()) => log::info!("Reloaded TLS certificates"),
+                    Err(err) => log::error!("Failed to reload TLS certificates: {err:#}"),
+                }
+            }
+        });
+    } else {
+        log::info!("Listening on {worker_addr}");
+    }
 
     let mut sigterm = signal(SignalKind::terminate()).expect("Failed to install sighandler");
     let mut sigint = signal(SignalKind::interrupt()).expect("Failed to install sighandler");
 
-    File::create("./ol-wasm.ready").expect("Failed to create ready file");
+    // Already listen while loading, so that orchestrators can poll `/ready`
+    load_functions(&config.registry_path, &function_mgr).await?;
+
+    let registry_watcher = RegistryWatcher::new(&config.registry_path, function_mgr.clone())
+        .with_context(|| "Failed to watch registry")?;
+    tokio::spawn(registry_watcher.run());
+
+    status.set_state(WorkerState::Ready);
+    log::info!("Loaded {} function(s); ready", function_mgr.num_functions());
+
+    // Deprecated in favor of `/ready`, but still written for existing tooling
+    File::create(READY_FILE).expect("Failed to create ready file");
+
+    for trigger in directory_triggers {
+        tokio::spawn(Service::run_directory_trigger(
+            trigger,
+            scheduler_service.clone(),
+        ));
+    }
+
+    if !scheduler_service.scheduler.is_empty() {
+        tokio::spawn(Service::run_scheduler(scheduler_service));
+    }
 
     tokio::select! {
-        result = fut => {
+        result = &mut fut => {
             if let Err(err) = result {
                 log::error!("Got server error: {err}");
             }
@@ -324,13 +1726,42 @@ async fn main_func(args: Args) -> anyhow::Result<()> {
         }
     }
 
+    // Make sure no new requests are routed to this worker before draining
+    status.set_state(WorkerState::ShuttingDown);
+    fut.abort();
+
+    if let Err(err) = remove_file(READY_FILE) {
+        log::warn!("Failed to remove ready file: {err}");
+    }
+
+    let grace_period = Duration::from_millis(config.shutdown_grace_period_ms);
+    let num_running = in_flight.num_running();
+
+    if num_running > 0 {
+        log::info!(
+            "Waiting up to {}ms for {num_running} running call(s) to finish",
+            grace_period.as_millis()
+        );
+    }
+
+    match in_flight.drain(grace_period).await {
+        0 => log::info!("All calls finished. Shutdown complete."),
+        num_running => log::warn!(
+            "Grace period expired with {num_running} call(s) still running. Shutting down anyway."
+        ),
+    }
+
     #[cfg(feature = "cpuprofiler")]
     if enable_cpu_profiler {
         let mut profiler = cpuprofiler::PROFILER.lock().unwrap();
         profiler.stop().expect("Failed to stop profiler");
     }
 
-    remove_file("./ol-wasm.ready").unwrap();
+    if let WorkerAddr::Unix(path) = &worker_addr {
+        if let Err(err) = remove_file(path.as_path()) {
+            log::warn!("Failed to remove socket at {path:?}: {err}");
+        }
+    }
 
     Ok(())
 }
@@ -344,6 +1775,7 @@ fn main() -> anyhow::Result<()> {
 
     let rt = runtime::Builder::new_multi_thread()
         .enable_io()
+        .enable_time()
         .worker_threads(num_threads)
         .build()
         .unwrap();
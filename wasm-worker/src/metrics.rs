@@ -0,0 +1,76 @@
+use lazy_static::lazy_static;
+
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, Encoder, HistogramVec, IntCounterVec,
+    TextEncoder,
+};
+
+lazy_static! {
+    /// Time it takes to create (and initialize) a new instance
+    pub static ref INSTANTIATE_TIME: HistogramVec = register_histogram_vec!(
+        "ol_wasm_instantiate_seconds",
+        "Time it takes to create a new WebAssembly instance",
+        &["function"]
+    )
+    .unwrap();
+
+    /// Time the guest spends executing its entry point
+    pub static ref EXECUTION_TIME: HistogramVec = register_histogram_vec!(
+        "ol_wasm_execution_seconds",
+        "Time it takes to execute a function",
+        &["function"]
+    )
+    .unwrap();
+
+    /// Guests that tried to exceed their memory or table limits
+    pub static ref LIMIT_VIOLATIONS: IntCounterVec = register_int_counter_vec!(
+        "ol_wasm_limit_violations_total",
+        "Number of times a guest exceeded one of its resource limits",
+        &["function", "resource"]
+    )
+    .unwrap();
+
+    /// Calls rejected because of a function or client rate limit
+    pub static ref RATE_LIMITED: IntCounterVec = register_int_counter_vec!(
+        "ol_wasm_rate_limited_total",
+        "Number of calls rejected because they exceeded a rate limit",
+        &["function", "scope"]
+    )
+    .unwrap();
+
+    /// Runs of scheduled functions by outcome ("success", "error", or "skipped")
+    pub static ref SCHEDULED_RUNS: IntCounterVec = register_int_counter_vec!(
+        "ol_wasm_scheduled_runs_total",
+        "Number of runs of scheduled functions",
+        &["schedule", "outcome"]
+    )
+    .unwrap();
+
+    /// Files handled by directory triggers by outcome ("done", "failed", or "retry")
+    pub static ref FILE_TRIGGERS: IntCounterVec = register_int_counter_vec!(
+        "ol_wasm_file_trigger_calls_total",
+        "Number of files processed by directory triggers",
+        &["function", "outcome"]
+    )
+    .unwrap();
+
+    /// Calls from guests into the host, such as `function_call` or `http_get`
+    pub static ref HOST_CALLS: IntCounterVec = register_int_counter_vec!(
+        "ol_wasm_host_calls_total",
+        "Number of calls from guests into the host",
+        &["call"]
+    )
+    .unwrap();
+}
+
+/// Renders all registered metrics in the Prometheus text format
+pub fn encode() -> anyhow::Result<Vec<u8>> {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    Ok(buffer)
+}
+
+/// The content type of the output of `encode`
+pub fn content_type() -> String {
+    TextEncoder::new().format_type().to_string()
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use hyper::header::HeaderMap;
+
+use parking_lot::Mutex;
+
+use serde::Deserialize;
+
+use crate::auth::constant_time_eq;
+use crate::settings::FunctionSettings;
+
+/// Marks calls that guests make through `function_call`
+///
+/// These were already counted against the limit of the original client.
+pub const INTERNAL_CALL_HEADER: &str = "x-ol-internal-call";
+
+/// A random value that proves that a call was made by one of this worker's guests
+pub fn internal_call_token() -> &'static str {
+    static TOKEN: OnceLock<String> = OnceLock::new();
+    TOKEN.get_or_init(|| uuid::Uuid::new_v4().simple().to_string())
+}
+
+/// Removes the internal call marker from the headers and returns whether it was valid
+pub fn take_internal_call_marker(headers: &mut HeaderMap) -> bool {
+    match headers.remove(INTERNAL_CALL_HEADER) {
+        Some(value) => constant_time_eq(value.as_bytes(), internal_call_token().as_bytes()),
+        None => false,
+    }
+}
+
+/// Limits for individual clients
+///
+/// Clients are identified by their API key or token subject if they
+/// authenticated, and by their IP address otherwise.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// How many calls each client may make per second (across all functions)
+    pub client_requests_per_sec: Option<f64>,
+    /// How many calls a client may make at once after being idle
+    pub client_burst: Option<u32>,
+    /// Maximum number of clients to keep track of; any others share a single bucket
+    pub max_clients: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            client_requests_per_sec: None,
+            client_burst: None,
+            max_clients: 100000,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(rate) = self.client_requests_per_sec {
+            Rate::new(rate, self.client_burst)?;
+        } else if self.client_burst.is_some() {
+            anyhow::bail!("\"rate_limits.client_burst\" requires \"client_requests_per_sec\"");
+        }
+
+        if self.max_clients == 0 {
+            anyhow::bail!("\"rate_limits.max_clients\" must be greater than zero");
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rate {
+    per_sec: f64,
+    burst: u32,
+}
+
+impl Rate {
+    /// The burst defaults to one second worth of calls
+    pub fn new(per_sec: f64, burst: Option<u32>) -> anyhow::Result<Self> {
+        if !(per_sec > 0.0 && per_sec.is_finite()) {
+            anyhow::bail!("Invalid rate limit {per_sec}; must be a positive number");
+        }
+
+        let burst = burst.unwrap_or_else(|| (per_sec.ceil() as u32).max(1));
+        if burst == 0 {
+            anyhow::bail!("Rate limit burst must be greater than zero");
+        }
+
+        Ok(Self { per_sec, burst })
+    }
+}
+
+struct TokenBucket {
+    rate: Rate,
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: Rate, now: Instant) -> Self {
+        Self {
+            rate,
+            tokens: rate.burst as f64,
+            last_update: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_update)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate.per_sec).min(self.rate.burst as f64);
+        self.last_update = now;
+    }
+
+    /// Applies a changed rate, keeping the tokens collected so far
+    fn set_rate(&mut self, rate: Rate, now: Instant) {
+        if self.rate != rate {
+            self.refill(now);
+            self.rate = rate;
+            self.tokens = self.tokens.min(rate.burst as f64);
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.tokens >= self.rate.burst as f64
+    }
+
+    fn quota(&self) -> Quota {
+        Quota {
+            limit: self.rate.burst,
+            remaining: self.tokens.floor() as u32,
+        }
+    }
+
+    /// How long until the next call is allowed
+    fn retry_after(&self) -> Duration {
+        Duration::from_secs_f64((1.0 - self.tokens).max(0.0) / self.rate.per_sec)
+    }
+}
+
+/// What is left of a rate limit after a call
+#[derive(Clone, Copy, Debug)]
+pub struct Quota {
+    pub limit: u32,
+    pub remaining: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum LimitScope {
+    Function,
+    Client,
+}
+
+impl LimitScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Function => "function",
+            Self::Client => "client",
+        }
+    }
+}
+
+/// A call was rejected because it exceeded a rate limit
+#[derive(Debug)]
+pub struct RateLimitExceeded {
+    pub scope: LimitScope,
+    pub limit: u32,
+    pub retry_after: Duration,
+}
+
+/// Enforces rate limits using token buckets
+///
+/// Every function and every client has its own bucket; a call must be allowed by both.
+pub struct RateLimiter {
+    client_rate: Option<Rate>,
+    max_clients: usize,
+    functions: Mutex<HashMap<String, TokenBucket>>,
+    clients: Mutex<HashMap<String, TokenBucket>>,
+    /// Shared by all clients that do not fit into `clients` anymore
+    overflow: Mutex<Option<TokenBucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> anyhow::Result<Self> {
+        let client_rate = match config.client_requests_per_sec {
+            Some(rate) => Some(Rate::new(rate, config.client_burst)?),
+            None => None,
+        };
+
+        Ok(Self {
+            client_rate,
+            max_clients: config.max_clients,
+            functions: Default::default(),
+            clients: Default::default(),
+            overflow: Mutex::new(client_rate.map(|rate| TokenBucket::new(rate, Instant::now()))),
+        })
+    }
+
+    /// Takes a token from the buckets of the function and the client
+    ///
+    /// Nothing is taken if either bucket is empty. Returns the tighter of
+    /// the two quotas, or `None` if no limit applies to this call.
+    pub fn check(
+        &self,
+        function: &str,
+        settings: &FunctionSettings,
+        client: Option<&str>,
+    ) -> Result<Option<Quota>, RateLimitExceeded> {
+        let now = Instant::now();
+
+        let function_rate = settings
+            .rate_limit
+            .map(|per_sec| Rate::new(per_sec, settings.rate_limit_burst))
+            .transpose()
+            .unwrap_or_else(|err| {
+                log::error!("Ignoring rate limit of function \"{function}\": {err}");
+                None
+            });
+
+        let mut functions = self.functions.lock();
+        let mut clients = self.clients.lock();
+        let mut overflow = self.overflow.lock();
+
+        let function_bucket = match function_rate {
+            Some(rate) => {
+                let bucket = functions
+                    .entry(function.to_string())
+                    .or_insert_with(|| TokenBucket::new(rate, now));
+                // The function might have been reconfigured
+                bucket.set_rate(rate, now);
+                bucket.refill(now);
+                Some(bucket)
+            }
+            None => None,
+        };
+
+        let client_bucket = match (self.client_rate, client) {
+            (Some(rate), Some(client)) => {
+                if clients.len() < self.max_clients || clients.contains_key(client) {
+                    let bucket = clients
+                        .entry(client.to_string())
+                        .or_insert_with(|| TokenBucket::new(rate, now));
+                    bucket.refill(now);
+                    Some(bucket)
+                } else {
+                    // Idle clients are removed by `remove_idle_clients`
+                    log::warn!("Too many clients to track; using shared limit for \"{client}\"");
+                    overflow.as_mut().map(|bucket| {
+                        bucket.refill(now);
+                        bucket
+                    })
+                }
+            }
+            _ => None,
+        };
+
+        for (scope, bucket) in [
+            (LimitScope::Function, &function_bucket),
+            (LimitScope::Client, &client_bucket),
+        ] {
+            if let Some(bucket) = bucket {
+                if bucket.tokens < 1.0 {
+                    return Err(RateLimitExceeded {
+                        scope,
+                        limit: bucket.rate.burst,
+                        retry_after: bucket.retry_after(),
+                    });
+                }
+            }
+        }
+
+        let mut quota: Option<Quota> = None;
+        for bucket in [function_bucket, client_bucket].into_iter().flatten() {
+            bucket.tokens -= 1.0;
+
+            let bucket_quota = bucket.quota();
+            quota = match quota {
+                Some(other) if other.remaining <= bucket_quota.remaining => Some(other),
+                _ => Some(bucket_quota),
+            };
+        }
+
+        Ok(quota)
+    }
+
+    /// Number of clients whose buckets are currently tracked
+    pub fn num_clients(&self) -> usize {
+        self.clients.lock().len()
+    }
+
+    /// Stops tracking clients whose buckets are full, as they behave the same as new ones
+    pub fn remove_idle_clients(&self) {
+        let now = Instant::now();
+
+        self.clients.lock().retain(|_, bucket| {
+            bucket.refill(now);
+            !bucket.is_full()
+        });
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use hyper::StatusCode;
+
+use parking_lot::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::metrics;
+use crate::versions::is_valid_name;
+
+/// How far ahead to look for the next match of a cron expression
+///
+/// Expressions such as `0 0 30 2 *` never match and would otherwise loop forever.
+const MAX_LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+/// A function that is invoked periodically
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleConfig {
+    /// Identifies the schedule in the logs and at `GET /schedules` (defaults to the function)
+    pub name: Option<String>,
+    pub function: String,
+    /// Five fields (minute, hour, day of month, month, day of week) in UTC, or a
+    /// shorthand such as `@hourly`
+    pub cron: String,
+    /// The arguments of every run, encoded as JSON (no arguments if unset)
+    pub payload: Option<serde_json::Value>,
+}
+
+impl ScheduleConfig {
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.function)
+    }
+}
+
+/// Checks that all schedules can be parsed and have distinct names
+pub fn validate(configs: &[ScheduleConfig]) -> anyhow::Result<()> {
+    Scheduler::new(configs).map(|_| ())
+}
+
+/// A parsed cron expression
+#[derive(Clone, Debug)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// If both day fields are restricted, a day has to match only one of them (like in cron)
+    days_restricted: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> anyhow::Result<Self> {
+        let expr = match expr.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            expr => expr,
+        };
+
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields.as_slice() else {
+            anyhow::bail!(
+                "Expected five fields (minute, hour, day of month, month, day of week) but got {}",
+                fields.len()
+            );
+        };
+
+        // Both 0 and 7 refer to Sunday
+        let days_of_week = parse_field(day_of_week, 0, 7, "day of week")?;
+        let days_of_week = (days_of_week | (days_of_week >> 7)) & 0x7f;
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59, "minute")?,
+            hours: parse_field(hour, 0, 23, "hour")?,
+            days_of_month: parse_field(day_of_month, 1, 31, "day of month")?,
+            months: parse_field(month, 1, 12, "month")?,
+            days_of_week,
+            days_restricted: !day_of_month.starts_with('*') && !day_of_week.starts_with('*'),
+        })
+    }
+
+    /// The first matching minute after `time` (in seconds since the UNIX epoch)
+    pub fn next_after(&self, time: u64) -> Option<u64> {
+        let mut minute = (time / 60) as i64 + 1;
+        let limit = minute + MAX_LOOKAHEAD_DAYS * MINUTES_PER_DAY;
+
+        // Skip whole months, days, and hours at a time if they do not match
+        while minute < limit {
+            let days = minute.div_euclid(MINUTES_PER_DAY);
+            let (year, month, day) = civil_from_days(days);
+
+            if self.months & (1 << month) == 0 {
+                let (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+                minute = days_from_civil(year, month, 1) * MINUTES_PER_DAY;
+                continue;
+            }
+
+            // 1970-01-01 was a Thursday
+            let weekday = (days + 4).rem_euclid(7);
+            if !self.matches_day(day, weekday as u32) {
+                minute = (days + 1) * MINUTES_PER_DAY;
+                continue;
+            }
+
+            if self.hours & (1 << (minute / 60 % 24)) == 0 {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+
+            if self.minutes & (1 << (minute % 60)) == 0 {
+                minute += 1;
+                continue;
+            }
+
+            return Some(minute as u64 * 60);
+        }
+
+        None
+    }
+
+    fn matches_day(&self, day_of_month: u32, day_of_week: u32) -> bool {
+        let day_of_month = self.days_of_month & (1 << day_of_month) != 0;
+        let day_of_week = self.days_of_week & (1 << day_of_week) != 0;
+
+        if self.days_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+/// Parses a comma-separated list of values, ranges (`a-b`), and steps (`*/n` or `a-b/n`)
+fn parse_field(field: &str, min: u32, max: u32, what: &str) -> anyhow::Result<u64> {
+    let parse_value = |value: &str| {
+        value
+            .parse::<u32>()
+            .with_context(|| format!("Invalid {what} \"{value}\""))
+    };
+
+    let mut values = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => anyhow::bail!("Invalid step \"{step}\" for {what}"),
+            },
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start)?, parse_value(end)?)
+        } else {
+            // `a/n` means every n-th value starting at a
+            let start = parse_value(range)?;
+            (start, if step > 1 { max } else { start })
+        };
+
+        if start < min || end > max || start > end {
+            anyhow::bail!("Invalid {what} \"{part}\"; must be within {min}-{max}");
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            values |= 1 << value;
+        }
+    }
+
+    Ok(values)
+}
+
+/// Converts days since the UNIX epoch to (year, month, day)
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400;
+
+    (
+        if month <= 2 { year + 1 } else { year },
+        month as u32,
+        day as u32,
+    )
+}
+
+/// Converts (year, month, day) to days since the UNIX epoch
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// The outcome of a scheduled run
+#[derive(Clone, Debug, Serialize)]
+pub struct RunRecord {
+    pub invocation_id: String,
+    /// When the run started (in seconds since the UNIX epoch)
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub status: u16,
+    pub success: bool,
+}
+
+#[derive(Default)]
+struct ScheduleState {
+    next_run: Option<u64>,
+    running: bool,
+    num_runs: u64,
+    /// Runs that were due while the previous one was still going
+    num_skipped: u64,
+    last_run: Option<RunRecord>,
+}
+
+pub struct Schedule {
+    pub name: String,
+    pub function: String,
+    pub args: Vec<u8>,
+    cron_expr: String,
+    cron: CronSchedule,
+    state: Mutex<ScheduleState>,
+}
+
+/// A run that has been started; the next run can only start once this is dropped
+pub struct ScheduledRun {
+    schedule: Arc<Schedule>,
+    started_at: u64,
+    start: Instant,
+}
+
+impl ScheduledRun {
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn finish(self, invocation_id: String, status: StatusCode) {
+        let mut state = self.schedule.state.lock();
+        state.num_runs += 1;
+        state.last_run = Some(RunRecord {
+            invocation_id,
+            started_at: self.started_at,
+            duration_ms: self.start.elapsed().as_millis() as u64,
+            status: status.as_u16(),
+            success: status.is_success(),
+        });
+    }
+}
+
+impl Drop for ScheduledRun {
+    fn drop(&mut self) {
+        self.schedule.state.lock().running = false;
+    }
+}
+
+/// The state of a schedule, as returned by `GET /schedules`
+#[derive(Debug, Serialize)]
+pub struct ScheduleStatus {
+    pub name: String,
+    pub function: String,
+    pub cron: String,
+    /// When the function will be invoked next (in seconds since the UNIX epoch)
+    pub next_run: Option<u64>,
+    pub running: bool,
+    pub num_runs: u64,
+    pub num_skipped: u64,
+    pub last_run: Option<RunRecord>,
+}
+
+/// Keeps track of when scheduled functions are due
+pub struct Scheduler {
+    schedules: Vec<Arc<Schedule>>,
+}
+
+impl Scheduler {
+    pub fn new(configs: &[ScheduleConfig]) -> anyhow::Result<Self> {
+        let now = unix_time();
+        let mut names = HashSet::new();
+        let mut schedules = vec![];
+
+        for config in configs {
+            let name = config.name();
+
+            if !is_valid_name(name) {
+                anyhow::bail!("Invalid schedule name \"{name}\"");
+            }
+            if !names.insert(name) {
+                anyhow::bail!("Duplicate schedule \"{name}\"; set distinct names");
+            }
+
+            let cron = CronSchedule::parse(&config.cron)
+                .with_context(|| format!("Invalid cron expression for schedule \"{name}\""))?;
+
+            let args = match &config.payload {
+                Some(payload) => serde_json::to_vec(payload).expect("Failed to serialize payload"),
+                None => vec![],
+            };
+
+            schedules.push(Arc::new(Schedule {
+                name: name.to_string(),
+                function: config.function.clone(),
+                args,
+                cron_expr: config.cron.clone(),
+                state: Mutex::new(ScheduleState {
+                    next_run: cron.next_after(now),
+                    ..Default::default()
+                }),
+                cron,
+            }));
+        }
+
+        Ok(Self { schedules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schedules.is_empty()
+    }
+
+    /// How long until the next schedule is due, or `None` if no schedule will ever run again
+    pub fn time_until_next_run(&self) -> Option<Duration> {
+        let next_run = self
+            .schedules
+            .iter()
+            .filter_map(|schedule| schedule.state.lock().next_run)
+            .min()?;
+
+        Some(Duration::from_secs(next_run.saturating_sub(unix_time())))
+    }
+
+    /// Starts all schedules that are due
+    ///
+    /// A schedule that is still running from last time is skipped until its next due time,
+    /// so that slow runs do not pile up.
+    pub fn start_due_runs(&self) -> Vec<ScheduledRun> {
+        let now = unix_time();
+        let mut runs = vec![];
+
+        for schedule in self.schedules.iter() {
+            let mut state = schedule.state.lock();
+
+            match state.next_run {
+                Some(next_run) if next_run <= now => {}
+                _ => continue,
+            }
+
+            state.next_run = schedule.cron.next_after(now);
+
+            if state.running {
+                log::warn!(
+                    "Skipping run of schedule \"{}\"; the previous run has not finished yet",
+                    schedule.name
+                );
+                state.num_skipped += 1;
+                metrics::SCHEDULED_RUNS
+                    .with_label_values(&[&schedule.name, "skipped"])
+                    .inc();
+            } else {
+                state.running = true;
+                runs.push(ScheduledRun {
+                    schedule: schedule.clone(),
+                    started_at: now,
+                    start: Instant::now(),
+                });
+            }
+        }
+
+        runs
+    }
+
+    pub fn status(&self) -> Vec<ScheduleStatus> {
+        self.schedules
+            .iter()
+            .map(|schedule| {
+                let state = schedule.state.lock();
+
+                ScheduleStatus {
+                    name: schedule.name.clone(),
+                    function: schedule.function.clone(),
+                    cron: schedule.cron_expr.clone(),
+                    next_run: state.next_run,
+                    running: state.running,
+                    num_runs: state.num_runs,
+                    num_skipped: state.num_skipped,
+                    last_run: state.last_run.clone(),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,146 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use hyper::server::conn::{http1, http2};
+
+use serde::Deserialize;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+use crate::support::{TokioExecutor, TokioIo};
+use crate::Service;
+
+/// The first bytes a client sends on an HTTP/2 connection (with prior knowledge)
+const HTTP2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// Which HTTP version(s) the worker speaks
+#[derive(Clone, Copy, Debug, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpProtocol {
+    /// Detect the version from the first bytes sent by the client
+    #[default]
+    Auto,
+    Http1,
+    Http2,
+}
+
+/// Serves all requests of a single client connection
+pub async fn serve_connection<I>(
+    mut io: I,
+    protocol: HttpProtocol,
+    service: Service,
+) -> anyhow::Result<()>
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (protocol, prefix) = match protocol {
+        HttpProtocol::Auto => detect_protocol(&mut io).await?,
+        protocol => (protocol, vec![]),
+    };
+
+    let io = TokioIo::new(Rewind { prefix, inner: io });
+
+    match protocol {
+        HttpProtocol::Http2 => {
+            http2::Builder::new(TokioExecutor)
+                .serve_connection(io, service)
+                .await?
+        }
+        _ => {
+            http1::Builder::new()
+                .keep_alive(true)
+                .serve_connection(io, service)
+                .await?
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks whether the client starts with the HTTP/2 preface
+///
+/// Also returns all bytes that were consumed, so they can be replayed.
+async fn detect_protocol<I: AsyncRead + Unpin>(io: &mut I) -> io::Result<(HttpProtocol, Vec<u8>)> {
+    let mut buffer = vec![0; HTTP2_PREFACE.len()];
+    let mut len = 0;
+
+    while len < HTTP2_PREFACE.len() {
+        let num_read = io.read(&mut buffer[len..]).await?;
+        if num_read == 0 {
+            break;
+        }
+
+        len += num_read;
+
+        // HTTP/1 requests diverge from the preface within the first few bytes
+        if !HTTP2_PREFACE.starts_with(&buffer[..len]) {
+            break;
+        }
+    }
+
+    buffer.truncate(len);
+
+    let protocol = if buffer == HTTP2_PREFACE {
+        HttpProtocol::Http2
+    } else {
+        HttpProtocol::Http1
+    };
+
+    log::trace!("Detected protocol {protocol:?} for new connection");
+
+    Ok((protocol, buffer))
+}
+
+/// Replays bytes that were read during protocol detection
+struct Rewind<T> {
+    prefix: Vec<u8>,
+    inner: T,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Rewind<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if !self.prefix.is_empty() {
+            let len = self.prefix.len().min(buf.remaining());
+            buf.put_slice(&self.prefix[..len]);
+            self.prefix.drain(..len);
+            return Poll::Ready(Ok(()));
+        }
+
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Rewind<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
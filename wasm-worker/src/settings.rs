@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::limits::InstanceLimits;
+
+/// Function name used to specify defaults for all functions
+pub const DEFAULT_FUNCTION: &str = "*";
+
+/// Settings that can be configured for each function individually
+///
+/// Unset fields fall back to the defaults (if any).
+#[derive(Clone, Debug, Default)]
+pub struct FunctionSettings {
+    /// Maximum wall-clock time a single invocation may take
+    pub timeout: Option<Duration>,
+    /// How much fuel a single invocation may consume
+    pub fuel: Option<u64>,
+    /// Yield to the runtime every time this much fuel has been consumed
+    pub fuel_yield_interval: Option<u64>,
+    /// Maximum size of an instance's linear memory in bytes
+    pub max_memory_bytes: Option<usize>,
+    /// Maximum number of elements in each of an instance's tables
+    pub max_table_elements: Option<u32>,
+    /// Maximum number of calls that may run at the same time
+    pub max_concurrency: Option<usize>,
+    /// Maximum number of calls that may wait for a slot once `max_concurrency` is reached
+    pub max_queue_length: Option<usize>,
+    /// How long a call may wait for a slot
+    pub queue_timeout: Option<Duration>,
+    /// How many calls per second the function accepts (from all clients combined)
+    pub rate_limit: Option<f64>,
+    /// How many calls the function accepts at once after being idle
+    pub rate_limit_burst: Option<u32>,
+}
+
+impl FunctionSettings {
+    pub fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        match key {
+            "timeout_ms" => {
+                self.timeout = Some(Duration::from_millis(parse_value(key, value)?));
+            }
+            "fuel" => self.fuel = Some(parse_value(key, value)?),
+            "fuel_yield_interval" => {
+                let interval: u64 = parse_value(key, value)?;
+                if interval == 0 {
+                    anyhow::bail!(
+                        "Invalid value \"{value}\" for setting \"{key}\": must be positive"
+                    );
+                }
+                self.fuel_yield_interval = Some(interval);
+            }
+            "max_memory_bytes" => self.max_memory_bytes = Some(parse_value(key, value)?),
+            "max_table_elements" => self.max_table_elements = Some(parse_value(key, value)?),
+            "max_concurrency" => self.max_concurrency = Some(parse_value(key, value)?),
+            "max_queue_length" => self.max_queue_length = Some(parse_value(key, value)?),
+            "queue_timeout_ms" => {
+                self.queue_timeout = Some(Duration::from_millis(parse_value(key, value)?));
+            }
+            "rate_limit" => {
+                let rate: f64 = parse_value(key, value)?;
+                if !(rate > 0.0 && rate.is_finite()) {
+                    anyhow::bail!(
+                        "Invalid value \"{value}\" for setting \"{key}\": must be positive"
+                    );
+                }
+                self.rate_limit = Some(rate);
+            }
+            "rate_limit_burst" => {
+                let burst: u32 = parse_value(key, value)?;
+                if burst == 0 {
+                    anyhow::bail!(
+                        "Invalid value \"{value}\" for setting \"{key}\": must be positive"
+                    );
+                }
+                self.rate_limit_burst = Some(burst);
+            }
+            _ => anyhow::bail!("Unknown function setting \"{key}\""),
+        }
+
+        Ok(())
+    }
+
+    pub fn get_instance_limits(&self) -> InstanceLimits {
+        InstanceLimits {
+            max_memory_bytes: self.max_memory_bytes,
+            max_table_elements: self.max_table_elements,
+        }
+    }
+
+    /// Fills all unset fields with the values from `defaults`
+    fn merge(&self, defaults: &FunctionSettings) -> Self {
+        Self {
+            timeout: self.timeout.or(defaults.timeout),
+            fuel: self.fuel.or(defaults.fuel),
+            fuel_yield_interval: self.fuel_yield_interval.or(defaults.fuel_yield_interval),
+            max_memory_bytes: self.max_memory_bytes.or(defaults.max_memory_bytes),
+            max_table_elements: self.max_table_elements.or(defaults.max_table_elements),
+            max_concurrency: self.max_concurrency.or(defaults.max_concurrency),
+            max_queue_length: self.max_queue_length.or(defaults.max_queue_length),
+            queue_timeout: self.queue_timeout.or(defaults.queue_timeout),
+            rate_limit: self.rate_limit.or(defaults.rate_limit),
+            rate_limit_burst: self.rate_limit_burst.or(defaults.rate_limit_burst),
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> anyhow::Result<T>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| anyhow::anyhow!("Invalid value \"{value}\" for setting \"{key}\": {err}"))
+}
+
+/// The settings of all functions
+#[derive(Default)]
+pub struct SettingsTable {
+    defaults: FunctionSettings,
+    functions: HashMap<String, FunctionSettings>,
+}
+
+impl SettingsTable {
+    /// Applies settings of the form `<function>.<setting>=<value>`
+    ///
+    /// These override any settings that were set before.
+    pub fn update(&mut self, entries: &[String]) -> anyhow::Result<()> {
+        for entry in entries {
+            let Some((target, value)) = entry.split_once('=') else {
+                anyhow::bail!(
+                    "Invalid function setting \"{entry}\"; expected <function>.<setting>=<value>"
+                );
+            };
+
+            let Some((name, key)) = target.split_once('.') else {
+                anyhow::bail!(
+                    "Invalid function setting \"{entry}\"; expected <function>.<setting>=<value>"
+                );
+            };
+
+            self.get_mut(name).set(key, value)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> &mut FunctionSettings {
+        if name == DEFAULT_FUNCTION {
+            &mut self.defaults
+        } else {
+            self.functions.entry(name.to_string()).or_default()
+        }
+    }
+
+    /// Returns the settings for the specified function, including defaults
+    pub fn get(&self, name: &str) -> FunctionSettings {
+        match self.functions.get(name) {
+            Some(settings) => settings.merge(&self.defaults),
+            None => self.defaults.clone(),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+
+use parking_lot::Mutex;
+
+use crate::functions::FunctionManager;
+
+/// How many latency samples to keep per function for computing percentiles
+const MAX_LATENCY_SAMPLES: usize = 1000;
+
+/// Invocation statistics for a single function
+#[derive(Default)]
+pub struct FunctionStats {
+    invocations: AtomicU64,
+    errors: AtomicU64,
+    cold_starts: AtomicU64,
+    warm_starts: AtomicU64,
+    total_latency_ms: AtomicU64,
+    fuel_consumed: AtomicU64,
+    latencies_ms: Mutex<VecDeque<u64>>,
+}
+
+impl FunctionStats {
+    pub fn record_start(&self, cold_start: bool) {
+        if cold_start {
+            self.cold_starts.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.warm_starts.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_fuel(&self, fuel: u64) {
+        self.fuel_consumed.fetch_add(fuel, Ordering::Relaxed);
+    }
+
+    pub fn record_invocation(&self, latency: Duration, success: bool) {
+        let latency_ms = latency.as_millis() as u64;
+
+        self.invocations.fetch_add(1, Ordering::Relaxed);
+        self.total_latency_ms
+            .fetch_add(latency_ms, Ordering::Relaxed);
+
+        if !success {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut latencies = self.latencies_ms.lock();
+        if latencies.len() >= MAX_LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency_ms);
+    }
+
+    fn write_snapshot(&self, name: &str, out: &mut BTreeMap<String, i64>) {
+        let invocations = self.invocations.load(Ordering::Relaxed);
+
+        out.insert(format!("{name}.cnt"), invocations as i64);
+        out.insert(
+            format!("{name}.errors"),
+            self.errors.load(Ordering::Relaxed) as i64,
+        );
+        out.insert(
+            format!("{name}.cold-starts"),
+            self.cold_starts.load(Ordering::Relaxed) as i64,
+        );
+        out.insert(
+            format!("{name}.warm-starts"),
+            self.warm_starts.load(Ordering::Relaxed) as i64,
+        );
+
+        out.insert(
+            format!("{name}.fuel-consumed"),
+            self.fuel_consumed.load(Ordering::Relaxed) as i64,
+        );
+
+        if invocations > 0 {
+            let total = self.total_latency_ms.load(Ordering::Relaxed);
+            out.insert(format!("{name}.ms-avg"), (total / invocations) as i64);
+        }
+
+        let mut latencies: Vec<u64> = self.latencies_ms.lock().iter().copied().collect();
+        if latencies.is_empty() {
+            return;
+        }
+
+        latencies.sort_unstable();
+
+        for percentile in [50, 90, 99] {
+            let idx = (latencies.len() * percentile / 100).min(latencies.len() - 1);
+            out.insert(format!("{name}.ms-p{percentile}"), latencies[idx] as i64);
+        }
+    }
+}
+
+/// Statistics for all functions of this worker
+///
+/// These are kept separately from the functions themselves,
+/// so that they are not reset when a function is reloaded.
+#[derive(Default)]
+pub struct Stats {
+    functions: DashMap<String, Arc<FunctionStats>>,
+}
+
+impl Stats {
+    pub fn get(&self, name: &str) -> Arc<FunctionStats> {
+        if let Some(entry) = self.functions.get(name) {
+            return entry.value().clone();
+        }
+
+        self.functions
+            .entry(name.to_string())
+            .or_default()
+            .value()
+            .clone()
+    }
+
+    /// Generates a snapshot in the same format as the Go worker's stats,
+    /// i.e., a flat map with keys of the form `<function>.<stat>`
+    pub fn snapshot(&self, function_mgr: &FunctionManager) -> BTreeMap<String, i64> {
+        let mut result = BTreeMap::default();
+
+        for entry in self.functions.iter() {
+            entry.value().write_snapshot(entry.key(), &mut result);
+        }
+
+        for function in function_mgr.get_functions() {
+            let name = function.info().id().to_string();
+
+            result.insert(
+                format!("{name}.idle-instances"),
+                function.num_idle_instances() as i64,
+            );
+            result.insert(
+                format!("{name}.busy-instances"),
+                function.num_busy_instances() as i64,
+            );
+            result.insert(
+                format!("{name}.queued-calls"),
+                function.num_queued_calls() as i64,
+            );
+        }
+
+        result
+    }
+}
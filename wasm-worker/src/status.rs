@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Instant;
+
+use serde::Serialize;
+
+use crate::drain::InFlightCalls;
+use crate::functions::{CacheStatus, FunctionManager, PoolingStatus};
+use crate::invocations::InvocationStore;
+use crate::ratelimit::RateLimiter;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerState {
+    /// Functions in the registry are still being loaded
+    Starting,
+    Ready,
+    /// Running calls are being drained
+    ShuttingDown,
+}
+
+/// Where the worker is in its lifecycle
+pub struct WorkerStatus {
+    started_at: Instant,
+    state: AtomicU8,
+}
+
+/// The body of `GET /status`
+#[derive(Debug, Serialize)]
+pub struct StatusReport {
+    pub version: &'static str,
+    pub state: WorkerState,
+    pub uptime_secs: u64,
+    /// Number of loaded modules (every version of a function counts separately)
+    pub num_functions: usize,
+    pub num_running_calls: usize,
+    /// Number of asynchronous invocations whose status or result is still kept
+    pub num_invocations: usize,
+    /// Number of clients whose rate limits are currently tracked
+    pub num_tracked_clients: usize,
+    pub cache: CacheStatus,
+    pub pooling: PoolingStatus,
+}
+
+impl WorkerStatus {
+    pub fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            state: AtomicU8::new(WorkerState::Starting as u8),
+        }
+    }
+
+    pub fn set_state(&self, state: WorkerState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+
+    pub fn state(&self) -> WorkerState {
+        match self.state.load(Ordering::SeqCst) {
+            state if state == WorkerState::Starting as u8 => WorkerState::Starting,
+            state if state == WorkerState::Ready as u8 => WorkerState::Ready,
+            _ => WorkerState::ShuttingDown,
+        }
+    }
+
+    pub fn report(
+        &self,
+        function_mgr: &FunctionManager,
+        in_flight: &InFlightCalls,
+        invocations: &InvocationStore,
+        rate_limiter: &RateLimiter,
+    ) -> StatusReport {
+        StatusReport {
+            version: env!("CARGO_PKG_VERSION"),
+            state: self.state(),
+            uptime_secs: self.started_at.elapsed().as_secs(),
+            num_functions: function_mgr.num_functions(),
+            num_running_calls: in_flight.num_running(),
+            num_invocations: invocations.num_invocations(),
+            num_tracked_clients: rate_limiter.num_clients(),
+            cache: function_mgr.cache_status(),
+            pooling: function_mgr.pooling_status(),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use parking_lot::RwLock;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::TlsConfig;
+use crate::listener::Connection;
+use crate::server::HttpProtocol;
+
+/// Terminates TLS for incoming connections
+///
+/// The certificates can be reloaded at runtime, e.g., after they have been rotated.
+pub struct TlsTerminator {
+    config: TlsConfig,
+    acceptor: RwLock<TlsAcceptor>,
+}
+
+impl TlsTerminator {
+    pub fn new(config: TlsConfig) -> anyhow::Result<Self> {
+        let acceptor = RwLock::new(load_acceptor(&config)?);
+        Ok(Self { config, acceptor })
+    }
+
+    /// Reads the certificates again
+    ///
+    /// Existing connections keep using the old certificates.
+    pub fn reload(&self) -> anyhow::Result<()> {
+        let acceptor = load_acceptor(&self.config)?;
+        *self.acceptor.write() = acceptor;
+        Ok(())
+    }
+
+    /// Performs the TLS handshake for a new connection
+    ///
+    /// Also returns the HTTP version the client selected using ALPN (if any).
+    /// Clients that do not complete the handshake in time are disconnected.
+    pub async fn accept(
+        &self,
+        conn: Connection,
+    ) -> std::io::Result<(TlsStream<Connection>, Option<HttpProtocol>)> {
+        let acceptor = self.acceptor.read().clone();
+        let timeout = Duration::from_millis(self.config.handshake_timeout_ms);
+
+        let stream = match tokio::time::timeout(timeout, acceptor.accept(conn)).await {
+            Ok(result) => result?,
+            Err(_) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    "Handshake timed out",
+                ))
+            }
+        };
+
+        let protocol = match stream.get_ref().1.alpn_protocol() {
+            Some(b"h2") => Some(HttpProtocol::Http2),
+            Some(b"http/1.1") => Some(HttpProtocol::Http1),
+            _ => None,
+        };
+
+        Ok((stream, protocol))
+    }
+}
+
+fn load_acceptor(config: &TlsConfig) -> anyhow::Result<TlsAcceptor> {
+    let certs = load_certs(&config.cert_path)?;
+    let key = load_private_key(&config.key_path)?;
+
+    let builder = ServerConfig::builder();
+
+    let builder = match &config.client_ca_path {
+        Some(path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(path)? {
+                roots
+                    .add(cert)
+                    .with_context(|| format!("Invalid client CA certificate in {path:?}"))?;
+            }
+
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .build()
+                .with_context(|| "Failed to set up client certificate verification")?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut server_config = builder
+        .with_single_cert(certs, key)
+        .with_context(|| "Certificate and private key do not match")?;
+    server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    Ok(TlsAcceptor::from(Arc::new(server_config)))
+}
+
+fn load_certs(path: &Path) -> anyhow::Result<Vec<CertificateDer<'static>>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open certificate file {path:?}"))?;
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificates in {path:?}"))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("No certificates found in {path:?}");
+    }
+
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> anyhow::Result<PrivateKeyDer<'static>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open private key file {path:?}"))?;
+
+    match rustls_pemfile::private_key(&mut BufReader::new(file))
+        .with_context(|| format!("Failed to parse private key in {path:?}"))?
+    {
+        Some(key) => Ok(key),
+        None => anyhow::bail!("No private key found in {path:?}"),
+    }
+}
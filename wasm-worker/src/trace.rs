@@ -0,0 +1,391 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use hyper::header::{HeaderMap, HeaderValue};
+
+use serde::Deserialize;
+use serde_json::json;
+
+/// The W3C header carrying the trace context
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// How many finished spans may wait to be written before new ones are dropped
+const MAX_PENDING_SPANS: usize = 4096;
+
+/// Reported as `service.name` in OTLP files
+const SERVICE_NAME: &str = "ol-wasm-worker";
+
+const FLAG_SAMPLED: u8 = 0x01;
+
+static EXPORTER: OnceLock<SpanExporter> = OnceLock::new();
+
+/// Where spans are written to
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TracingConfig {
+    pub exporter: ExporterKind,
+    pub path: PathBuf,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        Self {
+            exporter: ExporterKind::None,
+            path: "./ol-spans.jsonl".into(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExporterKind {
+    /// Spans are not recorded (the context is still propagated)
+    None,
+    /// One JSON object per span and line
+    Jsonl,
+    /// One OTLP/JSON `ExportTraceServiceRequest` per line, as written by the collector's file exporter
+    OtlpFile,
+}
+
+/// Identifies a span within a trace, as sent in the `traceparent` header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Starts a new trace
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_id(),
+            span_id: random_id(),
+            flags: FLAG_SAMPLED,
+        }
+    }
+
+    /// Parses a `traceparent` header of the form `00-<trace id>-<span id>-<flags>`
+    ///
+    /// Returns `None` if the header is malformed, in which case a new trace should be started.
+    pub fn parse(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+
+        let version = parse_hex::<1>(parts.next()?)?[0];
+        let trace_id = parse_hex::<16>(parts.next()?)?;
+        let span_id = parse_hex::<8>(parts.next()?)?;
+        let flags = parse_hex::<1>(parts.next()?)?[0];
+
+        // Future versions may append fields, but version 0xff is invalid
+        if version == 0xff || (version == 0 && parts.next().is_some()) {
+            return None;
+        }
+
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+
+        Some(Self {
+            trace_id,
+            span_id,
+            flags,
+        })
+    }
+
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(Self::parse)
+    }
+
+    /// A new span in the same trace
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id,
+            span_id: random_id(),
+            flags: self.flags,
+        }
+    }
+
+    pub fn to_traceparent(self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            to_hex(&self.trace_id),
+            to_hex(&self.span_id),
+            self.flags
+        )
+    }
+
+    pub fn to_header_value(self) -> HeaderValue {
+        HeaderValue::try_from(self.to_traceparent()).expect("traceparent is always valid")
+    }
+
+    pub fn is_sampled(&self) -> bool {
+        self.flags & FLAG_SAMPLED != 0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SpanKind {
+    /// Handling a request from a client
+    Server,
+    /// A request to another function or service
+    Client,
+}
+
+/// A unit of work within a trace, exported once it ends
+pub struct Span {
+    context: TraceContext,
+    parent_span_id: Option<[u8; 8]>,
+    name: String,
+    kind: SpanKind,
+    start_time: SystemTime,
+    attributes: Vec<(&'static str, serde_json::Value)>,
+}
+
+impl Span {
+    /// Starts a span as a child of `parent`, or a new trace if there is no parent
+    pub fn start(name: String, kind: SpanKind, parent: Option<&TraceContext>) -> Self {
+        let context = match parent {
+            Some(parent) => parent.child(),
+            None => TraceContext::new_root(),
+        };
+
+        Self {
+            context,
+            parent_span_id: parent.map(|parent| parent.span_id),
+            name,
+            kind,
+            start_time: SystemTime::now(),
+            attributes: vec![],
+        }
+    }
+
+    /// The context to propagate to work caused by this span
+    pub fn context(&self) -> &TraceContext {
+        &self.context
+    }
+
+    pub fn set_attribute<V: Into<serde_json::Value>>(&mut self, key: &'static str, value: V) {
+        self.attributes.push((key, value.into()));
+    }
+
+    /// Finishes the span and hands it to the exporter (if any)
+    pub fn end(self, is_error: bool) {
+        let Some(exporter) = EXPORTER.get() else {
+            return;
+        };
+
+        if !self.context.is_sampled() {
+            return;
+        }
+
+        let record = SpanRecord {
+            span: self,
+            end_time: SystemTime::now(),
+            is_error,
+        };
+
+        match exporter.sender.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                log::warn!("Too many pending spans; dropping span");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                log::error!("Span exporter stopped; dropping span");
+            }
+        }
+    }
+}
+
+struct SpanRecord {
+    span: Span,
+    end_time: SystemTime,
+    is_error: bool,
+}
+
+impl SpanRecord {
+    fn to_jsonl(&self) -> serde_json::Value {
+        let span = &self.span;
+        let attributes: serde_json::Map<String, serde_json::Value> = span
+            .attributes
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+
+        json!({
+            "trace_id": to_hex(&span.context.trace_id),
+            "span_id": to_hex(&span.context.span_id),
+            "parent_span_id": span.parent_span_id.map(|id| to_hex(&id)),
+            "name": span.name,
+            "kind": match span.kind {
+                SpanKind::Server => "server",
+                SpanKind::Client => "client",
+            },
+            "start_time_unix_nano": unix_nanos(span.start_time),
+            "end_time_unix_nano": unix_nanos(self.end_time),
+            "status": if self.is_error { "error" } else { "ok" },
+            "attributes": attributes,
+        })
+    }
+
+    fn to_otlp(&self) -> serde_json::Value {
+        let span = &self.span;
+        let attributes: Vec<_> = span
+            .attributes
+            .iter()
+            .map(|(key, value)| json!({ "key": key, "value": otlp_value(value) }))
+            .collect();
+
+        let mut otlp_span = json!({
+            "traceId": to_hex(&span.context.trace_id),
+            "spanId": to_hex(&span.context.span_id),
+            "name": span.name,
+            "kind": match span.kind {
+                SpanKind::Server => 2,
+                SpanKind::Client => 3,
+            },
+            // 64-bit integers are encoded as strings in OTLP/JSON
+            "startTimeUnixNano": unix_nanos(span.start_time).to_string(),
+            "endTimeUnixNano": unix_nanos(self.end_time).to_string(),
+            "attributes": attributes,
+            "status": { "code": if self.is_error { 2 } else { 1 } },
+        });
+
+        if let Some(parent_span_id) = span.parent_span_id {
+            otlp_span["parentSpanId"] = to_hex(&parent_span_id).into();
+        }
+
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [
+                        { "key": "service.name", "value": { "stringValue": SERVICE_NAME } }
+                    ]
+                },
+                "scopeSpans": [{
+                    "scope": { "name": SERVICE_NAME },
+                    "spans": [otlp_span],
+                }],
+            }]
+        })
+    }
+}
+
+struct SpanExporter {
+    sender: SyncSender<SpanRecord>,
+}
+
+/// Sets up the exporter for all spans of this process
+///
+/// Spans are written by a background thread, so that calls never wait for the disk.
+pub fn init(config: &TracingConfig) -> anyhow::Result<()> {
+    if config.exporter == ExporterKind::None {
+        return Ok(());
+    }
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&config.path)
+        .with_context(|| format!("Failed to open span file at {:?}", config.path))?;
+
+    let (sender, receiver) = sync_channel(MAX_PENDING_SPANS);
+    let kind = config.exporter;
+
+    std::thread::Builder::new()
+        .name("span-exporter".to_string())
+        .spawn(move || write_spans(kind, receiver, BufWriter::new(file)))
+        .with_context(|| "Failed to start span exporter")?;
+
+    if EXPORTER.set(SpanExporter { sender }).is_err() {
+        anyhow::bail!("Span exporter was already set up");
+    }
+
+    log::info!("Writing spans to {:?}", config.path);
+    Ok(())
+}
+
+fn write_spans<W: Write>(kind: ExporterKind, receiver: Receiver<SpanRecord>, mut writer: W) {
+    while let Ok(record) = receiver.recv() {
+        // Write everything that is pending before flushing
+        for record in std::iter::once(record).chain(receiver.try_iter()) {
+            let line = match kind {
+                ExporterKind::Jsonl => record.to_jsonl(),
+                ExporterKind::OtlpFile => record.to_otlp(),
+                ExporterKind::None => unreachable!(),
+            };
+
+            if let Err(err) = serde_json::to_writer(&mut writer, &line)
+                .map_err(std::io::Error::from)
+                .and_then(|()| writer.write_all(b"\n"))
+            {
+                log::error!("Failed to write span: {err}");
+            }
+        }
+
+        if let Err(err) = writer.flush() {
+            log::error!("Failed to write spans: {err}");
+        }
+    }
+}
+
+fn otlp_value(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Bool(value) => json!({ "boolValue": value }),
+        serde_json::Value::Number(value) if value.is_i64() || value.is_u64() => {
+            json!({ "intValue": value.to_string() })
+        }
+        serde_json::Value::Number(value) => json!({ "doubleValue": value }),
+        serde_json::Value::String(value) => json!({ "stringValue": value }),
+        other => json!({ "stringValue": other.to_string() }),
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or(0)
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    loop {
+        let mut id = [0; N];
+        rand::Rng::fill(&mut rand::thread_rng(), &mut id[..]);
+
+        // All-zero IDs are invalid
+        if id != [0; N] {
+            return id;
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
+
+/// Parses exactly `N` bytes of lowercase hex
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != 2 * N || !hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (pos, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * pos..2 * pos + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
@@ -0,0 +1,305 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use parking_lot::Mutex;
+
+use serde::Deserialize;
+
+use tokio::sync::mpsc;
+use tokio::time::{Instant, Interval};
+
+/// Files that were processed successfully are moved here (relative to the watched directory)
+const DONE_DIR: &str = "done";
+
+/// Files whose call failed are moved here (relative to the watched directory)
+const FAILED_DIR: &str = "failed";
+
+/// Invokes a function for every file that is added to a directory
+///
+/// Files stay in the directory until their call has finished, so files that were
+/// not processed before a restart (or crash) are picked up again.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DirectoryTriggerConfig {
+    pub function: String,
+    pub path: PathBuf,
+    /// Whether the function receives the file's path (as JSON) or its contents
+    #[serde(default)]
+    pub args: FileArgs,
+    /// Larger files are moved to the failed directory without invoking the function
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+    /// How often to look for files that were missed or need to be retried
+    #[serde(default = "default_rescan_interval_secs")]
+    pub rescan_interval_secs: u64,
+    /// How many files of this directory are processed at the same time
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileArgs {
+    /// `{"path": "<path of the file>"}`
+    #[default]
+    Path,
+    /// The raw contents of the file
+    Contents,
+}
+
+fn default_max_file_bytes() -> u64 {
+    16 * 1024 * 1024
+}
+
+fn default_rescan_interval_secs() -> u64 {
+    30
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+pub fn validate(configs: &[DirectoryTriggerConfig]) -> anyhow::Result<()> {
+    let mut paths = HashSet::new();
+
+    for config in configs {
+        if config.function.is_empty() {
+            anyhow::bail!("Directory trigger for {:?} has no function", config.path);
+        }
+        if config.rescan_interval_secs == 0 {
+            anyhow::bail!(
+                "\"rescan_interval_secs\" of directory triggers must be greater than zero"
+            );
+        }
+        if config.max_concurrency == 0 {
+            anyhow::bail!("\"max_concurrency\" of directory triggers must be greater than zero");
+        }
+        if !paths.insert(&config.path) {
+            anyhow::bail!("Directory {:?} is watched more than once", config.path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Watches a directory and hands out the files that need to be processed
+pub struct DirectoryTrigger {
+    // Needs to be kept alive for events to be generated
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    config: DirectoryTriggerConfig,
+    rescan_timer: Interval,
+    pending: VecDeque<PathBuf>,
+    in_progress: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+/// A file whose call is running; no other call is started for it until this is dropped
+pub struct TriggeredFile {
+    path: PathBuf,
+    directory: PathBuf,
+    in_progress: Arc<Mutex<HashSet<PathBuf>>>,
+}
+
+impl DirectoryTrigger {
+    pub fn new(config: DirectoryTriggerConfig) -> anyhow::Result<Self> {
+        for dir in [
+            config.path.clone(),
+            config.path.join(DONE_DIR),
+            config.path.join(FAILED_DIR),
+        ] {
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("Failed to create directory {dir:?}"))?;
+        }
+
+        let (sender, events) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Only fails if the receiver is gone, which happens on shutdown
+            let _ = sender.send(event);
+        })?;
+
+        watcher
+            .watch(&config.path, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {:?}", config.path))?;
+
+        log::info!(
+            "Invoking \"{}\" for new files in {:?}",
+            config.function,
+            config.path
+        );
+
+        let rescan_interval = Duration::from_secs(config.rescan_interval_secs);
+
+        let mut trigger = Self {
+            _watcher: watcher,
+            events,
+            rescan_timer: tokio::time::interval_at(
+                Instant::now() + rescan_interval,
+                rescan_interval,
+            ),
+            config,
+            pending: Default::default(),
+            in_progress: Default::default(),
+        };
+
+        // Files that were added while the worker was down (or not processed before it stopped)
+        trigger.rescan();
+
+        Ok(trigger)
+    }
+
+    pub fn config(&self) -> &DirectoryTriggerConfig {
+        &self.config
+    }
+
+    /// Waits for the next file that is not being processed yet
+    pub async fn next_file(&mut self) -> Option<TriggeredFile> {
+        loop {
+            while let Some(path) = self.pending.pop_front() {
+                if !is_candidate(&path) {
+                    continue;
+                }
+
+                // Files can be reported more than once, e.g., by a rescan while they are processed
+                if !self.in_progress.lock().insert(path.clone()) {
+                    continue;
+                }
+
+                return Some(TriggeredFile {
+                    path,
+                    directory: self.config.path.clone(),
+                    in_progress: self.in_progress.clone(),
+                });
+            }
+
+            tokio::select! {
+                event = self.events.recv() => match event {
+                    Some(Ok(event)) => self.handle_event(event),
+                    Some(Err(err)) => {
+                        log::error!("Failed to watch {:?}: {err}", self.config.path);
+                    }
+                    None => return None,
+                },
+                _ = self.rescan_timer.tick() => self.rescan(),
+            }
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event.kind {
+            // Only process files once they have been fully written
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                self.pending.extend(event.paths);
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                self.pending.extend(event.paths.into_iter().nth(1));
+            }
+            _ => {}
+        }
+    }
+
+    fn rescan(&mut self) {
+        let directory = match fs::read_dir(&self.config.path) {
+            Ok(directory) => directory,
+            Err(err) => {
+                log::error!("Failed to read {:?}: {err}", self.config.path);
+                return;
+            }
+        };
+
+        let mut paths: Vec<_> = directory.flatten().map(|entry| entry.path()).collect();
+
+        // Process older files first
+        paths.sort_by_key(|path| {
+            fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .unwrap_or(UNIX_EPOCH)
+        });
+
+        self.pending.extend(paths);
+    }
+}
+
+/// Skips directories (such as `done`) and hidden files
+///
+/// Writers should create files under a hidden name and rename them once complete.
+fn is_candidate(path: &Path) -> bool {
+    let is_hidden = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map_or(true, |name| name.starts_with('.'));
+
+    !is_hidden && path.is_file()
+}
+
+impl TriggeredFile {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Builds the arguments of the call
+    pub fn get_args(&self, config: &DirectoryTriggerConfig) -> anyhow::Result<Vec<u8>> {
+        match config.args {
+            FileArgs::Path => {
+                let path = self
+                    .path
+                    .to_str()
+                    .with_context(|| "Path is not valid UTF-8")?;
+                Ok(serde_json::to_vec(&serde_json::json!({ "path": path }))
+                    .expect("Failed to serialize path"))
+            }
+            FileArgs::Contents => {
+                let size = fs::metadata(&self.path)?.len();
+                if size > config.max_file_bytes {
+                    anyhow::bail!(
+                        "File has {size} bytes, but at most {} are allowed",
+                        config.max_file_bytes
+                    );
+                }
+
+                Ok(fs::read(&self.path)?)
+            }
+        }
+    }
+
+    /// Moves the file to the done or failed directory
+    pub fn finish(self, success: bool) {
+        let target_dir = self
+            .directory
+            .join(if success { DONE_DIR } else { FAILED_DIR });
+        let file_name = self.path.file_name().expect("Triggered files have a name");
+
+        // Keep earlier files with the same name
+        let mut target = target_dir.join(file_name);
+        if target.exists() {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_millis())
+                .unwrap_or(0);
+
+            let mut file_name = file_name.to_os_string();
+            file_name.push(format!(".{timestamp}"));
+            target = target_dir.join(file_name);
+        }
+
+        if let Err(err) = fs::rename(&self.path, &target) {
+            // The file will be processed again after the next rescan
+            log::error!("Failed to move {:?} to {target:?}: {err}", self.path);
+        }
+    }
+}
+
+impl Drop for TriggeredFile {
+    fn drop(&mut self) {
+        self.in_progress.lock().remove(&self.path);
+    }
+}
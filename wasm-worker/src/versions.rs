@@ -0,0 +1,155 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+use parking_lot::RwLock;
+
+use rand::distributions::{Distribution, WeightedIndex};
+
+/// The alias that is used when a function is called without a version or alias
+pub const DEFAULT_ALIAS: &str = "default";
+
+/// Maps versions to their share of the traffic
+pub type VersionWeights = BTreeMap<u64, u32>;
+
+type FunctionAliases = BTreeMap<String, VersionWeights>;
+
+/// Identifies a specific module of a function, e.g., `hashing` or `hashing@3`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FunctionId {
+    pub name: String,
+    pub version: Option<u64>,
+}
+
+impl FunctionId {
+    pub fn new(name: &str, version: Option<u64>) -> Self {
+        Self {
+            name: name.to_string(),
+            version,
+        }
+    }
+
+    /// Parses a function identifier, returns None if it is invalid
+    ///
+    /// Aliases are not valid identifiers; use `FunctionManager::get_function` to resolve them.
+    pub fn parse(id: &str) -> Option<Self> {
+        let (name, version) = match id.split_once('@') {
+            Some((name, version)) => (name, Some(version.parse().ok()?)),
+            None => (id, None),
+        };
+
+        if is_valid_name(name) {
+            Some(Self::new(name, version))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(version) = self.version {
+            write!(f, "{}@{version}", self.name)
+        } else {
+            write!(f, "{}", self.name)
+        }
+    }
+}
+
+/// Function and alias names end up in file names and URLs,
+/// so only allow a safe subset of characters
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Named aliases (such as `prod` or `canary`) that point to one or multiple versions of a function
+///
+/// The table is persisted as JSON next to the registry, so it survives restarts.
+pub struct AliasTable {
+    path: PathBuf,
+    aliases: RwLock<HashMap<String, FunctionAliases>>,
+}
+
+impl AliasTable {
+    pub fn load(path: PathBuf) -> anyhow::Result<Self> {
+        let aliases = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("Failed to parse aliases at {path:?}"))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::default(),
+            Err(err) => anyhow::bail!("Failed to read aliases at {path:?}: {err}"),
+        };
+
+        Ok(Self {
+            path,
+            aliases: RwLock::new(aliases),
+        })
+    }
+
+    pub fn get(&self, name: &str) -> FunctionAliases {
+        self.aliases.read().get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn set(&self, name: &str, alias: &str, weights: VersionWeights) -> anyhow::Result<()> {
+        if !is_valid_name(alias) || alias.parse::<u64>().is_ok() {
+            anyhow::bail!("Invalid alias \"{alias}\"");
+        }
+
+        if weights.is_empty() || weights.values().all(|weight| *weight == 0) {
+            anyhow::bail!("Alias \"{alias}\" must point to at least one version");
+        }
+
+        let mut aliases = self.aliases.write();
+        aliases
+            .entry(name.to_string())
+            .or_default()
+            .insert(alias.to_string(), weights);
+
+        self.store(&aliases)
+    }
+
+    pub fn remove(&self, name: &str, alias: &str) -> anyhow::Result<bool> {
+        let mut aliases = self.aliases.write();
+
+        let Some(entry) = aliases.get_mut(name) else {
+            return Ok(false);
+        };
+
+        if entry.remove(alias).is_none() {
+            return Ok(false);
+        }
+
+        if entry.is_empty() {
+            aliases.remove(name);
+        }
+
+        self.store(&aliases)?;
+        Ok(true)
+    }
+
+    /// Picks one of the versions the alias points to according to their weights
+    pub fn pick(&self, name: &str, alias: &str) -> Option<u64> {
+        let aliases = self.aliases.read();
+        let weights = aliases.get(name)?.get(alias)?;
+
+        if weights.len() == 1 {
+            return weights.keys().next().copied();
+        }
+
+        let dist = WeightedIndex::new(weights.values()).ok()?;
+        let idx = dist.sample(&mut rand::thread_rng());
+
+        weights.keys().nth(idx).copied()
+    }
+
+    fn store(&self, aliases: &HashMap<String, FunctionAliases>) -> anyhow::Result<()> {
+        let data = serde_json::to_vec_pretty(aliases)?;
+        fs::write(&self.path, data)
+            .with_context(|| format!("Failed to write aliases to {:?}", self.path))
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use tokio::sync::mpsc;
+
+use crate::functions::FunctionManager;
+use crate::versions::FunctionId;
+
+/// Returns the function name for a registry entry, if it is a WebAssembly file
+pub fn get_function_name(path: &Path) -> Option<String> {
+    if path.extension()? != "wasm" {
+        return None;
+    }
+
+    let id = FunctionId::parse(path.file_stem()?.to_str()?)?;
+    Some(id.to_string())
+}
+
+/// Watches the registry directory and (re-)loads functions as they change
+pub struct RegistryWatcher {
+    // Needs to be kept alive for events to be generated
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    function_mgr: Arc<FunctionManager>,
+}
+
+impl RegistryWatcher {
+    pub fn new(registry_path: &str, function_mgr: Arc<FunctionManager>) -> anyhow::Result<Self> {
+        let (sender, events) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Only fails if the receiver is gone, which happens on shutdown
+            let _ = sender.send(event);
+        })?;
+
+        watcher.watch(Path::new(registry_path), RecursiveMode::NonRecursive)?;
+
+        log::debug!("Watching registry at {registry_path:?} for changes");
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            function_mgr,
+        })
+    }
+
+    pub async fn run(mut self) {
+        while let Some(event) = self.events.recv().await {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    log::error!("Failed to watch registry: {err}");
+                    continue;
+                }
+            };
+
+            match event.kind {
+                // Only load files once they have been fully written
+                EventKind::Access(AccessKind::Close(AccessMode::Write))
+                | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                    for path in event.paths {
+                        self.load(path).await;
+                    }
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::From)) | EventKind::Remove(_) => {
+                    for path in event.paths {
+                        self.unload(&path);
+                    }
+                }
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                    let mut paths = event.paths.into_iter();
+
+                    if let (Some(from), Some(to)) = (paths.next(), paths.next()) {
+                        self.unload(&from);
+                        self.load(to).await;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn load(&self, path: PathBuf) {
+        if get_function_name(&path).is_none() || !path.is_file() {
+            return;
+        }
+
+        if let Err(err) = self.function_mgr.load_function(path.clone()).await {
+            log::error!("Failed to reload function at {path:?}: {err:?}");
+        }
+    }
+
+    fn unload(&self, path: &Path) {
+        if let Some(name) = get_function_name(path) {
+            self.function_mgr.unload_function(&name);
+        }
+    }
+}